@@ -0,0 +1,180 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use super::{BacktrackingIterator, Record};
+
+/// Implement `BacktrackingIterator` for an adaptor by deferring to the iterator it
+/// wraps, which is in the `iter` field, and `Record` too if the wrapped iterator
+/// implements it
+macro_rules! backtracking_passthrough {
+  (<$($parameter:ident),*>, $a_type:ty, $($bounds:tt)*) => {
+    impl<$($parameter),*> BacktrackingIterator for $a_type where $($bounds)* {
+      type RefPoint = I::RefPoint;
+
+      fn get_ref_point(&self) -> I::RefPoint {
+        self.iter.get_ref_point()
+      }
+
+      fn get_oldest_point(&self) -> I::RefPoint {
+        self.iter.get_oldest_point()
+      }
+
+      fn backtrack(&mut self, point: I::RefPoint) {
+        self.iter.backtrack(point)
+      }
+
+      fn undo_peek(&mut self, point: I::RefPoint) {
+        self.iter.undo_peek(point)
+      }
+    }
+
+    impl<$($parameter),*> Record for $a_type where I: Record, $($bounds)* {
+      type RefPoint = <I as Record>::RefPoint;
+
+      fn get_ref_point(&self) -> Self::RefPoint {
+        Record::get_ref_point(&self.iter)
+      }
+
+      fn forget_before(&mut self, point: Self::RefPoint) {
+        self.iter.forget_before(point)
+      }
+
+      fn forget(&mut self) {
+        self.iter.forget()
+      }
+    }
+  }
+}
+
+/// A backtracking iterator which maps the items of another. See
+/// `BacktrackingIterator::bt_map`.
+pub struct BtMap<I, F> {
+  pub(crate) iter: I,
+  pub(crate) f: F,
+}
+
+impl<B, I, F> Iterator for BtMap<I, F> where I: BacktrackingIterator, F: FnMut(I::Item) -> B {
+  type Item = B;
+
+  fn next(&mut self) -> Option<B> {
+    self.iter.next().map(&mut self.f)
+  }
+}
+
+backtracking_passthrough!(<B, I, F>, BtMap<I, F>, I: BacktrackingIterator, F: FnMut(I::Item) -> B);
+
+/// A backtracking iterator which skips the items of another which don't match a
+/// predicate. See `BacktrackingIterator::bt_filter`.
+pub struct BtFilter<I, P> {
+  pub(crate) iter: I,
+  pub(crate) predicate: P,
+}
+
+impl<I, P> Iterator for BtFilter<I, P> where I: BacktrackingIterator, P: FnMut(&I::Item) -> bool {
+  type Item = I::Item;
+
+  fn next(&mut self) -> Option<I::Item> {
+    self.iter.by_ref().find(&mut self.predicate)
+  }
+}
+
+backtracking_passthrough!(<I, P>, BtFilter<I, P>, I: BacktrackingIterator, P: FnMut(&I::Item) -> bool);
+
+/// A backtracking iterator which maps the items of another, skipping those which map
+/// to `None`. See `BacktrackingIterator::bt_filter_map`.
+pub struct BtFilterMap<I, F> {
+  pub(crate) iter: I,
+  pub(crate) f: F,
+}
+
+impl<B, I, F> Iterator for BtFilterMap<I, F> where I: BacktrackingIterator, F: FnMut(I::Item) -> Option<B> {
+  type Item = B;
+
+  fn next(&mut self) -> Option<B> {
+    self.iter.by_ref().find_map(&mut self.f)
+  }
+}
+
+backtracking_passthrough!(<B, I, F>, BtFilterMap<I, F>, I: BacktrackingIterator, F: FnMut(I::Item) -> Option<B>);
+
+/// A backtracking iterator which calls a function on every item of another before
+/// yielding it. See `BacktrackingIterator::bt_inspect`.
+pub struct BtInspect<I, F> {
+  pub(crate) iter: I,
+  pub(crate) f: F,
+}
+
+impl<I, F> Iterator for BtInspect<I, F> where I: BacktrackingIterator, F: FnMut(&I::Item) {
+  type Item = I::Item;
+
+  fn next(&mut self) -> Option<I::Item> {
+    let item = self.iter.next()?;
+    (self.f)(&item);
+    Some(item)
+  }
+}
+
+backtracking_passthrough!(<I, F>, BtInspect<I, F>, I: BacktrackingIterator, F: FnMut(&I::Item));
+
+/// A backtracking iterator which yields the items of another along with the point
+/// before each one. See `BacktrackingIterator::positioned`.
+pub struct Positioned<I> {
+  pub(crate) iter: I,
+}
+
+impl<I> Iterator for Positioned<I> where I: BacktrackingIterator {
+  type Item = (I::RefPoint, I::Item);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let point = self.iter.get_ref_point();
+    self.iter.next().map(|item| (point, item))
+  }
+}
+
+backtracking_passthrough!(<I>, Positioned<I>, I: BacktrackingIterator);
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+  use crate::{BacktrackingIterator, BacktrackingRecorder, Cursor, Record};
+
+  #[test]
+  fn peek_through_adaptor_test() {
+    let mut rec = BacktrackingRecorder::new(1_u8..=3);
+    {
+      let mut bt = rec.copying().bt_map(|i| i * 2).bt_filter(|i| *i > 2);
+      assert!(bt.peek() == Some(4));
+      assert!(bt.next() == Some(4));
+      assert!(bt.get_ref_point() == 2);
+    }
+    // Peeking is not a backtrack
+    assert!(rec.stats().backtracks == 0);
+  }
+
+  #[test]
+  fn forget_through_adaptor_test() {
+    let mut bt = Cursor::from(1_u8..=3).bt_map(|i| i * 2);
+    bt.next();
+    bt.forget();
+    bt.start_again();
+    assert!(bt.get_oldest_point() == 1);
+    assert!(bt.next() == Some(4));
+  }
+}
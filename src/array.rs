@@ -0,0 +1,459 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use core::fmt;
+
+use super::{BacktrackingIterator, BacktrackingState, Record};
+use self::BacktrackingState::{Progressing, Backtracking};
+
+/// What an `ArrayRecorder` does when an item is pulled from the source while its
+/// history is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+  /// Leave the item in the source, and report that the history is full. This is the
+  /// default.
+  Error,
+  /// Forget the oldest item in the history to make room for the new one
+  EvictOldest,
+}
+
+/// The error produced when an `ArrayRecorder` needs to record an item, but its history
+/// is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistoryFull;
+
+impl fmt::Display for HistoryFull {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "The history of the recorder is full - forget some of it first")
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for HistoryFull {}
+
+/// A recorder like `BacktrackingRecorder`, which keeps its history in an inline ring
+/// buffer of `N` items instead of a `Vec`, so that it never allocates.
+///
+/// Once `N` items are remembered, recording another one is handled by the recorder's
+/// `OverflowPolicy`. `RefPoint`s count from the first item ever recorded, as they do
+/// for `BacktrackingRecorder`, so they stay valid as the oldest items are forgotten.
+/// ```
+/// extern crate backtracking_iterator;
+/// use backtracking_iterator::{ArrayRecorder, BacktrackingIterator, HistoryFull, Record};
+///
+/// let mut rec: ArrayRecorder<_, 2> = ArrayRecorder::new(1_u8..=4);
+/// {
+///   let mut bt = rec.copying();
+///   assert!(bt.try_next() == Ok(Some(1_u8)));
+///   assert!(bt.try_next() == Ok(Some(2_u8)));
+///   assert!(bt.try_next() == Err(HistoryFull));
+/// }
+///
+/// rec.forget();
+/// let mut bt = rec.copying();
+/// assert!(bt.next() == Some(3_u8));
+/// ```
+pub struct ArrayRecorder<Iter, const N: usize> where Iter: Iterator {
+  iterator: Iter,
+  buffer: [Option<Iter::Item>; N],
+  /// The index in the buffer of the oldest remembered item
+  head: usize,
+  len: usize,
+  /// The number of items which have been forgotten from the front of the history
+  forgotten: usize,
+  state: BacktrackingState,
+  policy: OverflowPolicy,
+}
+
+impl<Iter, const N: usize> ArrayRecorder<Iter, N> where Iter: Iterator {
+  /// Create an `ArrayRecorder` from an existing iterator, with an empty history.
+  pub fn new(iterator: Iter) -> Self {
+    ArrayRecorder {
+      iterator,
+      buffer: core::array::from_fn(|_| None),
+      head: 0,
+      len: 0,
+      forgotten: 0,
+      state: Progressing,
+      policy: OverflowPolicy::Error,
+    }
+  }
+
+  /// Set what the recorder does when its history is full.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::{ArrayRecorder, BacktrackingIterator, OverflowPolicy};
+  ///
+  /// let mut rec = ArrayRecorder::<_, 2>::new(1_u8..=3)
+  ///   .with_overflow_policy(OverflowPolicy::EvictOldest);
+  /// let mut bt = rec.copying();
+  /// assert!(bt.by_ref().count() == 3);
+  /// assert!(bt.get_oldest_point() == 1);
+  /// bt.start_again();
+  /// assert!(bt.next() == Some(2_u8));
+  /// ```
+  pub fn with_overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+    self.policy = policy;
+    self
+  }
+
+  /// The greatest number of items the history can hold
+  pub fn capacity(&self) -> usize {
+    N
+  }
+
+  /// The number of items in the history
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Whether the history has no items in it
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  pub fn copying(&mut self) -> ArrayCopyingIterator<'_, Iter, N> where Iter::Item: Clone {
+    ArrayCopyingIterator { recorder: self }
+  }
+
+  /// Produce an iterator which yields references into the history. References may
+  /// still be alive when the history is full, so this iterator never evicts items,
+  /// whatever the `OverflowPolicy` is.
+  pub fn referencing(&mut self) -> ArrayReferencingIterator<'_, Iter, N> {
+    ArrayReferencingIterator { recorder: self }
+  }
+
+  /// The `RefPoint` one past the newest item in the history
+  fn frontier(&self) -> usize {
+    self.forgotten + self.len
+  }
+
+  /// The item at the given point, if it is remembered
+  fn get(&self, point: usize) -> Option<&Iter::Item> {
+    if self.forgotten <= point && point < self.frontier() {
+      self.buffer[(self.head + point - self.forgotten) % N].as_ref()
+    } else {
+      None
+    }
+  }
+
+  /// Drop the oldest item in the history
+  fn drop_oldest(&mut self) {
+    self.buffer[self.head] = None;
+    self.head = (self.head + 1) % N;
+    self.len -= 1;
+    self.forgotten += 1;
+  }
+
+  /// Move the cursor on, and get the point of the item it moved past. Pulling a new
+  /// item when the history is full either evicts the oldest item, if `evict` is set,
+  /// or fails without touching the source.
+  fn advance(&mut self, evict: bool) -> Result<Option<usize>, HistoryFull> {
+    if let Backtracking { position } = self.state {
+      if position < self.frontier() {
+        self.state = Backtracking { position: position + 1 };
+        return Ok(Some(position));
+      }
+      trace_event!(point = self.frontier(), history_len = self.len, "frontier reached");
+      self.state = Progressing;
+    }
+
+    let evict = evict && N > 0;
+    if self.len == N && !evict {
+      return Err(HistoryFull);
+    }
+
+    match self.iterator.next() {
+      Some(val) => {
+        if self.len == N {
+          self.drop_oldest();
+        }
+        self.buffer[(self.head + self.len) % N] = Some(val);
+        self.len += 1;
+        Ok(Some(self.frontier() - 1))
+      },
+      None => {
+        debug_event!(frontier = self.frontier(), "source exhausted");
+        Ok(None)
+      },
+    }
+  }
+
+  fn get_oldest_point(&self) -> usize {
+    self.forgotten
+  }
+
+  /// Move the cursor back to the given point, if it is remembered
+  fn backtrack(&mut self, position: usize) {
+    if position >= self.forgotten && position != self.get_ref_point() {
+      trace_event!(from = self.get_ref_point(), to = position, history_len = self.len, "backtrack");
+      if position < self.frontier() {
+        trace_event!(point = position, frontier = self.frontier(), "replay started");
+      }
+      self.state = Backtracking { position };
+    }
+  }
+
+  /// Move the cursor back to where it was before a peek. This isn't traced as a
+  /// backtrack.
+  fn undo_peek(&mut self, position: usize) {
+    self.state = Backtracking { position };
+  }
+}
+
+/// Shows the items of an `ArrayRecorder`'s history as a list
+struct History<'record, Iter, const N: usize>(&'record ArrayRecorder<Iter, N>) where Iter: Iterator;
+
+impl<'record, Iter, const N: usize> fmt::Debug for History<'record, Iter, N> where Iter: Iterator, Iter::Item: fmt::Debug {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let recorder = self.0;
+    f.debug_list()
+      .entries((recorder.forgotten..recorder.frontier()).filter_map(|point| recorder.get(point)))
+      .finish()
+  }
+}
+
+impl<Iter, const N: usize> fmt::Debug for ArrayRecorder<Iter, N> where Iter: Iterator, Iter::Item: fmt::Debug {
+  /// Show the recorded history and the cursor. The source iterator is not shown.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("ArrayRecorder")
+      .field("history", &History(self))
+      .field("forgotten", &self.forgotten)
+      .field("state", &self.state)
+      .field("policy", &self.policy)
+      .finish_non_exhaustive()
+  }
+}
+
+#[cfg(feature = "alloc")]
+impl<Iter, const N: usize> ArrayRecorder<Iter, N> where Iter: Iterator, Iter::Item: fmt::Debug {
+  /// Render the remembered history for debugging - see
+  /// `BacktrackingRecorder::dump_history`.
+  pub fn dump_history(&self) -> alloc::string::String {
+    let history = (self.forgotten..self.frontier()).filter_map(|point| self.get(point));
+    crate::dump::dump_history(history, self.forgotten, self.get_ref_point(), self.frontier())
+  }
+}
+
+impl<Iter, const N: usize> Record for ArrayRecorder<Iter, N> where Iter: Iterator {
+  type RefPoint = usize;
+
+  fn get_ref_point(&self) -> usize {
+    match self.state {
+      Progressing => self.frontier(),
+      Backtracking { position } => position,
+    }
+  }
+
+  fn forget_before(&mut self, position: usize) {
+    if self.forgotten <= position && position <= self.frontier() {
+      debug_event!(before = position, items = position - self.forgotten, history_len = self.frontier() - position, "forget");
+      while self.forgotten < position {
+        self.drop_oldest();
+      }
+
+      if let Backtracking { position: current } = self.state {
+        if current < position {
+          self.state = Backtracking { position };
+        }
+      }
+    }
+  }
+}
+
+/// An iterator over the history of an `ArrayRecorder` which produces clones of the
+/// historical items
+pub struct ArrayCopyingIterator<'record, Iter, const N: usize> where Iter: Iterator, Iter::Item: Clone {
+  recorder: &'record mut ArrayRecorder<Iter, N>,
+}
+
+impl<'record, Iter, const N: usize> ArrayCopyingIterator<'record, Iter, N> where Iter: Iterator, Iter::Item: Clone {
+  /// Get the next item, or an error if it had to be pulled from the source and the
+  /// history was full. The item is left in the source, so it can be read once some
+  /// history has been forgotten.
+  pub fn try_next(&mut self) -> Result<Option<Iter::Item>, HistoryFull> {
+    let evict = self.recorder.policy == OverflowPolicy::EvictOldest;
+    Ok(self.recorder.advance(evict)?.map(|point| {
+      self.recorder.get(point).expect("The item just passed is remembered").clone()
+    }))
+  }
+}
+
+impl<'record, Iter, const N: usize> Iterator for ArrayCopyingIterator<'record, Iter, N> where Iter: Iterator, Iter::Item: Clone {
+  type Item = Iter::Item;
+
+  /// Get the next item. Panics if the history is full under `OverflowPolicy::Error` -
+  /// use `try_next` to handle that instead.
+  fn next(&mut self) -> Option<Iter::Item> {
+    match self.try_next() {
+      Ok(item) => item,
+      Err(full) => panic!("{}", full),
+    }
+  }
+}
+
+impl<'record, Iter, const N: usize> BacktrackingIterator for ArrayCopyingIterator<'record, Iter, N> where Iter: Iterator, Iter::Item: Clone {
+  type RefPoint = usize;
+
+  fn get_ref_point(&self) -> usize {
+    self.recorder.get_ref_point()
+  }
+
+  fn get_oldest_point(&self) -> usize {
+    self.recorder.get_oldest_point()
+  }
+
+  fn backtrack(&mut self, position: usize) {
+    self.recorder.backtrack(position);
+  }
+
+  fn undo_peek(&mut self, position: usize) {
+    self.recorder.undo_peek(position);
+  }
+}
+
+/// The copies are owned by the caller, so forgetting history while iterating is safe
+impl<'record, Iter, const N: usize> Record for ArrayCopyingIterator<'record, Iter, N> where Iter: Iterator, Iter::Item: Clone {
+  type RefPoint = usize;
+
+  fn get_ref_point(&self) -> usize {
+    self.recorder.get_ref_point()
+  }
+
+  fn forget_before(&mut self, position: usize) {
+    self.recorder.forget_before(position);
+  }
+}
+
+impl<'record, Iter, const N: usize> fmt::Debug for ArrayCopyingIterator<'record, Iter, N> where Iter: Iterator, Iter::Item: Clone + fmt::Debug {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("ArrayCopyingIterator")
+      .field("recorder", &*self.recorder)
+      .finish()
+  }
+}
+
+/// An iterator over the history of an `ArrayRecorder` which produces references to
+/// historical items
+pub struct ArrayReferencingIterator<'record, Iter, const N: usize> where Iter: Iterator {
+  recorder: &'record mut ArrayRecorder<Iter, N>,
+}
+
+impl<'record, Iter, const N: usize> ArrayReferencingIterator<'record, Iter, N> where Iter: Iterator, Iter::Item: 'record {
+  /// Get the next item, or an error if it had to be pulled from the source and the
+  /// history was full. The item is left in the source, so it can be read once some
+  /// history has been forgotten.
+  pub fn try_next(&mut self) -> Result<Option<&'record Iter::Item>, HistoryFull> {
+    Ok(self.recorder.advance(false)?.map(|point| {
+      let item = self.recorder.get(point).expect("The item just passed is remembered");
+      // A slot of the buffer is only overwritten after it has been forgotten, which
+      // needs the recorder to be borrowed mutably - and we hold that borrow for the
+      // whole of 'record. We never evict, so the item lives at least as long.
+      unsafe { &*(item as *const Iter::Item) }
+    }))
+  }
+}
+
+impl<'record, Iter, const N: usize> Iterator for ArrayReferencingIterator<'record, Iter, N> where Iter: Iterator, Iter::Item: 'record {
+  type Item = &'record Iter::Item;
+
+  /// Get the next item. Panics if the history is full - use `try_next` to handle
+  /// that instead.
+  fn next(&mut self) -> Option<&'record Iter::Item> {
+    match self.try_next() {
+      Ok(item) => item,
+      Err(full) => panic!("{}", full),
+    }
+  }
+}
+
+impl<'record, Iter, const N: usize> BacktrackingIterator for ArrayReferencingIterator<'record, Iter, N> where Iter: Iterator, Iter::Item: 'record {
+  type RefPoint = usize;
+
+  fn get_ref_point(&self) -> usize {
+    self.recorder.get_ref_point()
+  }
+
+  fn get_oldest_point(&self) -> usize {
+    self.recorder.get_oldest_point()
+  }
+
+  fn backtrack(&mut self, position: usize) {
+    self.recorder.backtrack(position);
+  }
+
+  fn undo_peek(&mut self, position: usize) {
+    self.recorder.undo_peek(position);
+  }
+}
+
+impl<'record, Iter, const N: usize> fmt::Debug for ArrayReferencingIterator<'record, Iter, N> where Iter: Iterator, Iter::Item: fmt::Debug {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("ArrayReferencingIterator")
+      .field("recorder", &*self.recorder)
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{BacktrackingIterator, Record};
+  use super::{ArrayRecorder, HistoryFull, OverflowPolicy};
+
+  #[test]
+  fn full_leaves_item_in_source_test() {
+    let mut rec: ArrayRecorder<_, 3> = ArrayRecorder::new(0_u8..10);
+    {
+      let mut bt = rec.referencing();
+      assert!(bt.by_ref().take(3).eq([0, 1, 2].iter()));
+      assert!(bt.try_next() == Err(HistoryFull));
+      bt.backtrack(1);
+      assert!(bt.next() == Some(&1));
+    }
+
+    // The ring wraps around after forgetting
+    rec.forget_before(2);
+    let mut bt = rec.copying();
+    assert!(bt.by_ref().take(3).eq(2..5));
+    assert!(bt.try_next() == Err(HistoryFull));
+    bt.backtrack(2);
+    assert!(bt.by_ref().take(3).eq(2..5));
+    assert!(bt.get_oldest_point() == 2);
+  }
+
+  #[test]
+  fn evict_keeps_ref_points_test() {
+    let mut rec = ArrayRecorder::<_, 4>::new(0_u32..100)
+      .with_overflow_policy(OverflowPolicy::EvictOldest);
+    let mut bt = rec.copying();
+    assert!(bt.by_ref().count() == 100);
+    assert!(bt.get_oldest_point() == 96);
+    bt.backtrack(98);
+    assert!(bt.collect::<Vec<_>>() == vec![98, 99]);
+  }
+
+  #[test]
+  fn zero_capacity_test() {
+    let mut rec = ArrayRecorder::<_, 0>::new(0_u8..2)
+      .with_overflow_policy(OverflowPolicy::EvictOldest);
+    assert!(rec.copying().try_next() == Err(HistoryFull));
+  }
+}
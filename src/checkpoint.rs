@@ -0,0 +1,122 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use alloc::vec::Vec;
+
+use serde::{Serialize, Serializer, Deserialize};
+
+use super::{BacktrackingState, BacktrackingRecorder, Expectations, Stats};
+use self::BacktrackingState::Backtracking;
+use crate::trace::ReplaySpan;
+
+/// The serializable state of a `BacktrackingRecorder` - its history, the number of
+/// items forgotten before the history, and the position of its cursor.
+///
+/// A `BacktrackingRecorder` serializes into this shape, and a deserialized checkpoint
+/// can be given back to `BacktrackingRecorder::resume` along with a source iterator.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecorderCheckpoint<Item> {
+  history: Vec<Item>,
+  forgotten: usize,
+  furthest: usize,
+  state: BacktrackingState,
+}
+
+/// A borrowing version of `RecorderCheckpoint`, so that serializing a recorder does
+/// not require cloning its history
+#[derive(Serialize)]
+struct RecorderCheckpointRef<'record, Item> {
+  history: &'record [Item],
+  forgotten: usize,
+  furthest: usize,
+  state: &'record BacktrackingState,
+}
+
+impl<Iter> Serialize for BacktrackingRecorder<Iter> where Iter: Iterator, Iter::Item: Serialize {
+  /// Serialize the history and cursor of the recorder. The source iterator is not
+  /// serialized.
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+    RecorderCheckpointRef {
+      history: &self.backtracking_vec,
+      forgotten: self.forgotten,
+      furthest: self.furthest,
+      state: &self.state,
+    }.serialize(serializer)
+  }
+}
+
+impl<Iter> BacktrackingRecorder<Iter> where Iter: Iterator {
+  /// Recreate a `BacktrackingRecorder` from a checkpoint of its history. The given
+  /// iterator must continue from where the checkpointed source left off - it will
+  /// be used to produce items after the recorded history. Expectations registered
+  /// with `expect` are not part of a checkpoint.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// extern crate serde_json;
+  /// use backtracking_iterator::{BacktrackingIterator, BacktrackingRecorder, RecorderCheckpoint};
+  ///
+  /// let mut rec = BacktrackingRecorder::new(1_u8..=4);
+  /// {
+  ///   let mut bt = rec.copying();
+  ///   bt.next(); // 1_u8
+  ///   bt.next(); // 2_u8
+  ///   bt.backtrack(1);
+  /// }
+  ///
+  /// let saved = serde_json::to_string(&rec).unwrap();
+  /// let checkpoint: RecorderCheckpoint<u8> = serde_json::from_str(&saved).unwrap();
+  ///
+  /// // The source is reopened from the first unrecorded item
+  /// let mut resumed = BacktrackingRecorder::resume(checkpoint, 3_u8..=4);
+  /// let mut bt = resumed.copying();
+  /// assert!(bt.next().unwrap() == 2_u8);
+  /// assert!(bt.next().unwrap() == 3_u8);
+  /// bt.start_again();
+  /// assert!(bt.next().unwrap() == 1_u8);
+  /// ```
+  pub fn resume(checkpoint: RecorderCheckpoint<Iter::Item>, iterator: Iter) -> Self {
+    let RecorderCheckpoint { history, forgotten, furthest, state } = checkpoint;
+
+    // A cursor before the forgotten items can't be replayed, so start from the
+    // oldest remembered item instead
+    let state = match state {
+      Backtracking { position } if position < forgotten => Backtracking { position: forgotten },
+      state => state,
+    };
+    let frontier = forgotten + history.len();
+    let current = match state {
+      Backtracking { position } => core::cmp::min(position, frontier),
+      _ => frontier,
+    };
+
+    BacktrackingRecorder {
+      iterator,
+      backtracking_vec: history,
+      forgotten,
+      furthest: core::cmp::max(furthest, current),
+      expectations: Expectations::new(),
+      stats: Stats::default(),
+      state,
+      replay: ReplaySpan::default(),
+      observer: None,
+    }
+  }
+}
@@ -19,70 +19,294 @@
  * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
-use super::{BacktrackingState, BacktrackingIterator};
+use super::{BacktrackingIterator, Record, Stats};
+use crate::segmented::SegmentedHistory;
+use crate::stats::AtomicStats;
+use crate::trace::ReplaySpan;
 
-use std::sync::{Arc, RwLock, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
-static EXPECT_RW: &'static str = "The read-write lock on the history has been poisoned by a thread panic!";
-static EXPECT_MUTEX: &'static str = "The mutual exclusion lock on the iterator has been poisoned by a thread panic!"; 
+static EXPECT_MUTEX: &str = "The mutual exclusion lock on the iterator has been poisoned by a thread panic!"; 
 
+/// How a concurrent iterator behaves when it reaches the end of its history after
+/// another thread panicked while pulling from the shared source. The source is never
+/// pulled from again after such a panic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecoveryPolicy {
+  /// `next()` panics, and `try_next()` returns an error. This is the default.
+  Panic,
+  /// Treat the source as exhausted, so that only the items already recorded are yielded
+  ContinueWithRecorded,
+  /// Treat the source as failed - `next()` returns `None`, and `try_next()` returns an
+  /// error
+  MarkFailed,
+}
+
+/// The error produced when the source of a concurrent iterator has been poisoned by
+/// a thread which panicked while pulling from it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourcePoisoned;
+
+impl std::fmt::Display for SourcePoisoned {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str(EXPECT_MUTEX)
+  }
+}
+
+impl std::error::Error for SourcePoisoned {}
+
+/// Lock the shared source, or decide what to do if it has been poisoned. `Ok(None)`
+/// means that the source should be treated as exhausted.
+fn lock_source<Iter>(iterator: &Mutex<Iter>, policy: RecoveryPolicy) -> Result<Option<MutexGuard<'_, Iter>>, SourcePoisoned> {
+  match iterator.lock() {
+    Ok(guard) => Ok(Some(guard)),
+    Err(_) if policy == RecoveryPolicy::ContinueWithRecorded => Ok(None),
+    Err(_) => Err(SourcePoisoned),
+  }
+}
+
+/// Turn the result of `try_next()` into the result of `next()`, following the policy
+fn next_or_recover<Item>(result: Result<Option<Item>, SourcePoisoned>, policy: RecoveryPolicy) -> Option<Item> {
+  match result {
+    Ok(item) => item,
+    Err(error) if policy == RecoveryPolicy::Panic => panic!("{}", error),
+    Err(_) => None,
+  }
+}
+
+/// A record of the oldest point that each live clone of a concurrent iterator may
+/// still backtrack to, and of the counters of each live clone. History before the
+/// oldest of these points is not needed by any clone.
+struct Registry {
+  holds: Vec<Option<usize>>,
+  /// The counters of each live clone, in the same slots as the holds
+  counters: Vec<Option<Arc<AtomicStats>>>,
+  /// The counts of the clones which have been dropped
+  retired: Stats,
+}
+
+impl Registry {
+  /// Lock the registry. The holds are always left consistent, so a panic in another
+  /// thread while it was locked doesn't matter.
+  fn lock(registry: &Mutex<Self>) -> MutexGuard<'_, Self> {
+    registry.lock().unwrap_or_else(PoisonError::into_inner)
+  }
+
+  fn new(hold: usize) -> (Arc<Mutex<Self>>, usize, Arc<AtomicStats>) {
+    let mut registry = Registry {
+      holds: vec![],
+      counters: vec![],
+      retired: Stats::default(),
+    };
+    let (slot, stats) = registry.register(hold);
+    (Arc::new(Mutex::new(registry)), slot, stats)
+  }
+
+  /// Register a new clone, returning its slot and its counters
+  fn register(&mut self, hold: usize) -> (usize, Arc<AtomicStats>) {
+    let stats = Arc::new(AtomicStats::default());
+    if let Some(slot) = self.holds.iter().position(Option::is_none) {
+      self.holds[slot] = Some(hold);
+      self.counters[slot] = Some(stats.clone());
+      (slot, stats)
+    } else {
+      self.holds.push(Some(hold));
+      self.counters.push(Some(stats.clone()));
+      (self.holds.len() - 1, stats)
+    }
+  }
+
+  /// Release the slot of a dropped clone, keeping its counts
+  fn retire(&mut self, slot: usize) {
+    self.holds[slot] = None;
+    if let Some(stats) = self.counters[slot].take() {
+      stats.add_to(&mut self.retired);
+    }
+  }
+
+  /// The oldest point held by any live clone
+  fn oldest_hold(&self) -> Option<usize> {
+    self.holds.iter().filter_map(|&hold| hold).min()
+  }
+
+  /// The counts of every clone, live or dropped
+  fn stats(&self, hold: usize) -> Stats {
+    let mut stats = self.retired;
+    for counters in self.counters.iter().flatten() {
+      counters.add_to(&mut stats);
+    }
+    stats.forgotten = self.oldest_hold().unwrap_or(hold);
+    stats
+  }
+}
+
+/// The items of the history from a clone's hold up to the frontier. A clone's hold
+/// is never reclaimed while the clone is alive.
+fn held_history<Item>(history: &SegmentedHistory<Item>, hold: usize) -> Vec<&Item> {
+  (hold..history.len())
+    .map(|index| history.get(index).expect("Held items can always be read"))
+    .collect()
+}
 
 impl<'item, Iter> From<Iter> for ConcurrentReferencingBacktrackingIterator<'item, Iter> where Iter: Iterator, Iter: 'item {
   /// Create a `ConcurrentReferencingBacktrackingIterator` from an existing iterator.
   fn from(iterator: Iter) -> Self {
+    let (registry, slot, stats) = Registry::new(0);
     ConcurrentReferencingBacktrackingIterator {
       item_marker: std::marker::PhantomData,
       iterator: Arc::new(Mutex::new(iterator)),
-      backtracking_vec: Arc::new(RwLock::new(vec![])),
+      backtracking_vec: Arc::new(SegmentedHistory::new()),
+      stats,
+      registry,
+      slot,
+      hold: 0,
       position: 0,
+      policy: RecoveryPolicy::Panic,
+      replay: ReplaySpan::default(),
     }
   }
 }
 
+/// A backtracking iterator which can be cloned and sent between threads, with every
+/// clone sharing the same history. Replaying the history doesn't take any locks - only
+/// pulling a new item from the source does.
+///
+/// Each clone holds on to the history from its oldest point, which starts out as the
+/// oldest point of the iterator it was cloned from, and is moved forward by `forget`.
+///
+/// Unlike `ConcurrentCopyingBacktrackingIterator`, this iterator never frees history
+/// behind the slowest clone. The references it yields may outlive the clone which
+/// yielded them, so the history is only freed once every clone has been dropped, and
+/// `forget` only stops a clone from backtracking. Use the copying iterator where the
+/// history has to stay bounded while the clones are alive.
 pub struct ConcurrentReferencingBacktrackingIterator<'item, Iter> where Iter: Iterator, Iter: 'item {
   item_marker: std::marker::PhantomData<&'item Iter::Item>,
   iterator: Arc<Mutex<Iter>>,
-  backtracking_vec: Arc<RwLock<Vec<Iter::Item>>>,
+  backtracking_vec: Arc<SegmentedHistory<Iter::Item>>,
+  stats: Arc<AtomicStats>,
+  registry: Arc<Mutex<Registry>>,
+  slot: usize,
+  hold: usize,
   position: usize,
+  policy: RecoveryPolicy,
+  replay: ReplaySpan,
 }
 
 impl<'item, Iter> Clone for ConcurrentReferencingBacktrackingIterator<'item, Iter> where Iter: Iterator, Iter: 'item {
   fn clone(&self) -> Self {
+    let (slot, stats) = Registry::lock(&self.registry).register(self.hold);
     ConcurrentReferencingBacktrackingIterator {
       item_marker: std::marker::PhantomData,
       iterator: self.iterator.clone(),
       backtracking_vec: self.backtracking_vec.clone(),
+      stats,
+      registry: self.registry.clone(),
+      slot,
+      hold: self.hold,
       position: self.position,
+      policy: self.policy,
+      replay: ReplaySpan::default(),
     }
   }
 }
 
-impl<'item, Iter> Iterator for ConcurrentReferencingBacktrackingIterator<'item, Iter> where Iter: Iterator, Iter: 'item {
-  type Item = &'item Iter::Item;
+impl<'item, Iter> std::fmt::Debug for ConcurrentReferencingBacktrackingIterator<'item, Iter> where Iter: Iterator, Iter: 'item, Iter::Item: std::fmt::Debug {
+  /// Show this clone's position and the history it holds
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.debug_struct("ConcurrentReferencingBacktrackingIterator")
+      .field("position", &self.position)
+      .field("hold", &self.hold)
+      .field("history", &held_history(&self.backtracking_vec, self.hold))
+      .field("policy", &self.policy)
+      .finish_non_exhaustive()
+  }
+}
 
-  fn next(&mut self) -> Option<&'item Iter::Item> {
+impl<'item, Iter> ConcurrentReferencingBacktrackingIterator<'item, Iter> where Iter: Iterator, Iter: 'item, Iter::Item: std::fmt::Debug {
+  /// Render the history held by this clone for debugging, with its position marked -
+  /// see `BacktrackingRecorder::dump_history`. History which this clone has forgotten
+  /// is shown as forgotten, even if other clones still hold it.
+  pub fn dump_history(&self) -> String {
+    let history = held_history(&self.backtracking_vec, self.hold);
+    crate::dump::dump_history(history, self.hold, self.position, self.backtracking_vec.len())
+  }
+}
+
+impl<'item, Iter> Drop for ConcurrentReferencingBacktrackingIterator<'item, Iter> where Iter: Iterator, Iter: 'item {
+  fn drop(&mut self) {
+    Registry::lock(&self.registry).retire(self.slot);
+  }
+}
+
+impl<'item, Iter> ConcurrentReferencingBacktrackingIterator<'item, Iter> where Iter: Iterator, Iter: 'item {
+  /// Set how this clone behaves if the source is poisoned by a panicking thread.
+  /// Clones made from this one will inherit the policy.
+  pub fn with_recovery_policy(mut self, policy: RecoveryPolicy) -> Self {
+    self.policy = policy;
+    self
+  }
+
+  /// How this iterator and all its clones have been used
+  pub fn stats(&self) -> Stats {
+    Registry::lock(&self.registry).stats(self.hold)
+  }
+
+  /// Get the next item, or an error if the source was needed and had been poisoned
+  /// by a panicking thread. This never panics because of another thread.
+  pub fn try_next(&mut self) -> Result<Option<&'item Iter::Item>, SourcePoisoned> {
+    /// Produce a reference into the history which should be valid for 'item.
+    /// The segments of the history never move, and this iterator never reclaims
+    /// them, so they live for as long as any clone does.
     macro_rules! unsafe_backtracking_index {
       ($index:expr) => {
         unsafe {
-          &*(&self.backtracking_vec.read().expect(EXPECT_RW)[$index] as *const Iter::Item)
+          &*(self.backtracking_vec.get($index).expect("Recorded items can always be read") as *const Iter::Item)
         }
       };
     }
 
-    use crate::{Backtracking, Progressing};
-    if self.position >= self.backtracking_vec.read().expect(EXPECT_RW).len() {
-      if let Some(val) = self.iterator.lock().expect(EXPECT_MUTEX).next() {
-        self.backtracking_vec.write().expect(EXPECT_RW).push(val);
-        self.position = self.position + 1;
-        Some(unsafe_backtracking_index!(self.backtracking_vec.read().expect(EXPECT_RW).len() - 1))
-      } else {
-        None
+    if self.position >= self.backtracking_vec.len() {
+      let mut iterator = match lock_source(&self.iterator, self.policy)? {
+        Some(iterator) => iterator,
+        None => return Ok(None),
+      };
+      // Another clone may have pulled the item while we waited for the iterator
+      if self.position >= self.backtracking_vec.len() {
+        let val = match iterator.next() {
+          Some(val) => val,
+          None => {
+            debug_event!(frontier = self.backtracking_vec.len(), "source exhausted");
+            return Ok(None);
+          },
+        };
+        // A position past the frontier continues from the frontier
+        self.position = self.backtracking_vec.len();
+        // Pushes are serialized by the lock on the iterator
+        unsafe { self.backtracking_vec.push(val) };
+        AtomicStats::count(&self.stats.pulled);
+        self.stats.history_len(self.backtracking_vec.len());
+        self.position += 1;
+        return Ok(Some(unsafe_backtracking_index!(self.position - 1)));
       }
-    } else {
-      let old_position = self.position;
-      self.position = self.position + 1;
-      Some(unsafe_backtracking_index!(old_position))
     }
+
+    AtomicStats::count(&self.stats.replayed);
+    let old_position = self.position;
+    self.position += 1;
+    if self.position == self.backtracking_vec.len() {
+      trace_event!(slot = self.slot, point = self.position, "frontier reached");
+      self.replay.close();
+    }
+    Ok(Some(unsafe_backtracking_index!(old_position)))
+  }
+}
+
+impl<'item, Iter> Iterator for ConcurrentReferencingBacktrackingIterator<'item, Iter> where Iter: Iterator, Iter: 'item {
+  type Item = &'item Iter::Item;
+
+  fn next(&mut self) -> Option<&'item Iter::Item> {
+    let result = self.try_next();
+    next_or_recover(result, self.policy)
   }
 }
 
@@ -94,41 +318,280 @@ impl<'item, Iter> BacktrackingIterator for ConcurrentReferencingBacktrackingIter
   }
 
   fn get_oldest_point(&self) -> usize {
-    // Always the oldest position
-    0_usize
+    self.hold
   }
 
   fn backtrack(&mut self, position: usize) {
+    if position >= self.hold && position != self.position {
+      trace_event!(slot = self.slot, from = self.position, to = position, history_len = self.backtracking_vec.len(), "backtrack");
+      if position < self.backtracking_vec.len() {
+        trace_event!(slot = self.slot, point = position, frontier = self.backtracking_vec.len(), "replay started");
+        self.replay.open(position, self.backtracking_vec.len());
+      }
+      self.position = position;
+      AtomicStats::count(&self.stats.backtracks);
+    }
+  }
+
+  fn undo_peek(&mut self, position: usize) {
     self.position = position;
   }
 }
 
-//// COPYING VERSION
+impl<'item, Iter> Record for ConcurrentReferencingBacktrackingIterator<'item, Iter> where Iter: Iterator, Iter: 'item {
+  type RefPoint = usize;
+
+  fn get_ref_point(&self) -> usize {
+    self.position
+  }
+
+  /// Stop this clone from backtracking before the given point. Other clones are
+  /// unaffected, and no history is freed - see the type documentation.
+  fn forget_before(&mut self, point: usize) {
+    if self.hold <= point && point <= self.position {
+      debug_event!(slot = self.slot, before = point, items = point - self.hold, history_len = self.backtracking_vec.len(), "forget");
+      self.hold = point;
+      AtomicStats::count(&self.stats.forgets);
+      Registry::lock(&self.registry).holds[self.slot] = Some(point);
+    }
+  }
+}
+
+// COPYING VERSION
 
 impl<Iter> From<Iter> for ConcurrentCopyingBacktrackingIterator<Iter> where Iter: Iterator, Iter::Item: Clone {
   /// Create a `ConcurrentCopyingBacktrackingIterator` from an existing iterator.
   fn from(iterator: Iter) -> Self {
+    let (registry, slot, stats) = Registry::new(0);
     ConcurrentCopyingBacktrackingIterator {
       iterator: Arc::new(Mutex::new(iterator)),
-      backtracking_vec: Arc::new(RwLock::new(vec![])),
+      backtracking_vec: Arc::new(SegmentedHistory::new()),
+      stats,
+      registry,
+      slot,
+      hold: 0,
       position: 0,
+      policy: RecoveryPolicy::Panic,
+      replay: ReplaySpan::default(),
     }
   }
 }
 
+/// A backtracking iterator which can be cloned and sent between threads, with every
+/// clone sharing the same history. Replaying the history doesn't take any locks - only
+/// pulling a new item from the source does.
+///
+/// Each clone holds on to the history from its oldest point, which starts out as the
+/// oldest point of the iterator it was cloned from, and is moved forward by `forget`.
+/// History before the oldest point held by any live clone is freed as soon as it is
+/// no longer held.
+/// ```
+/// extern crate backtracking_iterator;
+/// use backtracking_iterator::{BacktrackingIterator, Record};
+/// use backtracking_iterator::concurrent::ConcurrentCopyingBacktrackingIterator;
+///
+/// let mut fast = ConcurrentCopyingBacktrackingIterator::from(1_u8..=4);
+/// let mut slow = fast.clone();
+/// fast.next(); // 1_u8
+/// fast.next(); // 2_u8
+///
+/// // The slow clone can still see the history that the fast one has forgotten
+/// fast.forget();
+/// assert!(fast.get_oldest_point() == 2);
+/// assert!(slow.next().unwrap() == 1_u8);
+/// ```
 pub struct ConcurrentCopyingBacktrackingIterator<Iter> where Iter: Iterator, Iter::Item: Clone {
   iterator: Arc<Mutex<Iter>>,
-  backtracking_vec: Arc<RwLock<Vec<Iter::Item>>>,
+  backtracking_vec: Arc<SegmentedHistory<Iter::Item>>,
+  stats: Arc<AtomicStats>,
+  registry: Arc<Mutex<Registry>>,
+  slot: usize,
+  hold: usize,
   position: usize,
+  policy: RecoveryPolicy,
+  replay: ReplaySpan,
+}
+
+impl<Iter> ConcurrentCopyingBacktrackingIterator<Iter> where Iter: Iterator, Iter::Item: Clone {
+  /// Free the history which is no longer held by any live clone. The registry must
+  /// be locked, so that no clone can move its hold while this happens.
+  fn reclaim(&self, registry: &Registry) {
+    if let Some(oldest) = registry.oldest_hold() {
+      // No clone can read before its own hold, so nothing before the oldest hold
+      // will be read again
+      unsafe { self.backtracking_vec.reclaim_before(oldest) };
+    }
+  }
+
+  /// The `RefPoint` one past the newest recorded item
+  #[cfg(feature = "rayon")]
+  pub(crate) fn frontier(&self) -> usize {
+    self.backtracking_vec.len()
+  }
+
+  /// Pull from the source until the history is recorded up to `point` or the source
+  /// runs out. This moves the clone to the frontier without counting a backtrack.
+  #[cfg(feature = "rayon")]
+  pub(crate) fn record_until(&mut self, point: usize) {
+    while self.backtracking_vec.len() < point {
+      self.position = self.backtracking_vec.len();
+      if self.next().is_none() {
+        break;
+      }
+    }
+  }
+
+  /// Read a recorded item without moving this clone, if it is still held by this clone
+  #[cfg(feature = "rayon")]
+  pub(crate) fn recorded(&self, index: usize) -> Option<&Iter::Item> {
+    if index >= self.hold {
+      self.backtracking_vec.get(index)
+    } else {
+      None
+    }
+  }
 }
 
 impl<Iter> Clone for ConcurrentCopyingBacktrackingIterator<Iter> where Iter: Iterator, Iter::Item: Clone {
   fn clone(&self) -> Self {
+    let (slot, stats) = Registry::lock(&self.registry).register(self.hold);
     ConcurrentCopyingBacktrackingIterator {
       iterator: self.iterator.clone(),
       backtracking_vec: self.backtracking_vec.clone(),
+      stats,
+      registry: self.registry.clone(),
+      slot,
+      hold: self.hold,
       position: self.position,
+      policy: self.policy,
+      replay: ReplaySpan::default(),
+    }
+  }
+}
+
+impl<Iter> std::fmt::Debug for ConcurrentCopyingBacktrackingIterator<Iter> where Iter: Iterator, Iter::Item: Clone + std::fmt::Debug {
+  /// Show this clone's position and the history it holds
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.debug_struct("ConcurrentCopyingBacktrackingIterator")
+      .field("position", &self.position)
+      .field("hold", &self.hold)
+      .field("history", &held_history(&self.backtracking_vec, self.hold))
+      .field("policy", &self.policy)
+      .finish_non_exhaustive()
+  }
+}
+
+impl<Iter> ConcurrentCopyingBacktrackingIterator<Iter> where Iter: Iterator, Iter::Item: Clone + std::fmt::Debug {
+  /// Render the history held by this clone for debugging, with its position marked -
+  /// see `BacktrackingRecorder::dump_history`. History which this clone has forgotten
+  /// is shown as forgotten, even if other clones still hold it.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::concurrent::ConcurrentCopyingBacktrackingIterator;
+  ///
+  /// let mut bt = ConcurrentCopyingBacktrackingIterator::from(1_u8..=2);
+  /// bt.next();
+  /// assert!(bt.dump_history() == "  0: 1\n> 1: (frontier)\n");
+  /// ```
+  pub fn dump_history(&self) -> String {
+    let history = held_history(&self.backtracking_vec, self.hold);
+    crate::dump::dump_history(history, self.hold, self.position, self.backtracking_vec.len())
+  }
+}
+
+impl<Iter> Drop for ConcurrentCopyingBacktrackingIterator<Iter> where Iter: Iterator, Iter::Item: Clone {
+  fn drop(&mut self) {
+    let mut registry = Registry::lock(&self.registry);
+    registry.retire(self.slot);
+    self.reclaim(&registry);
+  }
+}
+
+impl<Iter> ConcurrentCopyingBacktrackingIterator<Iter> where Iter: Iterator, Iter::Item: Clone {
+  /// Set how this clone behaves if the source is poisoned by a panicking thread.
+  /// Clones made from this one will inherit the policy.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::concurrent::{ConcurrentCopyingBacktrackingIterator, RecoveryPolicy, SourcePoisoned};
+  ///
+  /// let source = (1_u8..=3).map(|i| if i == 3 { panic!("Bad item!") } else { i });
+  /// let mut bt = ConcurrentCopyingBacktrackingIterator::from(source)
+  ///   .with_recovery_policy(RecoveryPolicy::MarkFailed);
+  /// let mut worker = bt.clone();
+  ///
+  /// let crashed = std::thread::spawn(move || worker.by_ref().count()).join();
+  /// assert!(crashed.is_err());
+  ///
+  /// // The items recorded before the panic can still be used
+  /// assert!(bt.try_next() == Ok(Some(1_u8)));
+  /// assert!(bt.try_next() == Ok(Some(2_u8)));
+  /// assert!(bt.try_next() == Err(SourcePoisoned));
+  /// ```
+  pub fn with_recovery_policy(mut self, policy: RecoveryPolicy) -> Self {
+    self.policy = policy;
+    self
+  }
+
+  /// How this iterator and all its clones have been used. Items only count as
+  /// forgotten once every clone has forgotten them.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::{BacktrackingIterator, Record};
+  /// use backtracking_iterator::concurrent::ConcurrentCopyingBacktrackingIterator;
+  ///
+  /// let mut fast = ConcurrentCopyingBacktrackingIterator::from(1_u8..=4);
+  /// let mut slow = fast.clone();
+  /// fast.next();
+  /// fast.next();
+  /// slow.next();
+  /// fast.forget();
+  /// assert!(fast.stats().forgotten == 0);
+  /// slow.forget();
+  ///
+  /// let stats = slow.stats();
+  /// assert!(stats.pulled == 2 && stats.replayed == 1 && stats.forgets == 2);
+  /// assert!(stats.forgotten == 1 && stats.peak_history == 2);
+  /// ```
+  pub fn stats(&self) -> Stats {
+    Registry::lock(&self.registry).stats(self.hold)
+  }
+
+  /// Get the next item, or an error if the source was needed and had been poisoned
+  /// by a panicking thread. This never panics because of another thread.
+  pub fn try_next(&mut self) -> Result<Option<Iter::Item>, SourcePoisoned> {
+    if self.position >= self.backtracking_vec.len() {
+      let mut iterator = match lock_source(&self.iterator, self.policy)? {
+        Some(iterator) => iterator,
+        None => return Ok(None),
+      };
+      // Another clone may have pulled the item while we waited for the iterator
+      if self.position >= self.backtracking_vec.len() {
+        let val = match iterator.next() {
+          Some(val) => val,
+          None => {
+            debug_event!(frontier = self.backtracking_vec.len(), "source exhausted");
+            return Ok(None);
+          },
+        };
+        // A position past the frontier continues from the frontier
+        self.position = self.backtracking_vec.len();
+        // Pushes are serialized by the lock on the iterator
+        unsafe { self.backtracking_vec.push(val.clone()) };
+        AtomicStats::count(&self.stats.pulled);
+        self.stats.history_len(self.backtracking_vec.len() - self.backtracking_vec.reclaimed());
+        self.position += 1;
+        return Ok(Some(val));
+      }
+    }
+
+    AtomicStats::count(&self.stats.replayed);
+    let val = self.backtracking_vec.get(self.position).expect("Recorded items can always be read").clone();
+    self.position += 1;
+    if self.position == self.backtracking_vec.len() {
+      trace_event!(slot = self.slot, point = self.position, "frontier reached");
+      self.replay.close();
     }
+    Ok(Some(val))
   }
 }
 
@@ -136,21 +599,8 @@ impl<Iter> Iterator for ConcurrentCopyingBacktrackingIterator<Iter> where Iter:
   type Item = Iter::Item;
 
   fn next(&mut self) -> Option<Iter::Item> {
-    
-    use crate::{Backtracking, Progressing};
-    if self.position >= self.backtracking_vec.read().expect(EXPECT_RW).len() {
-      if let Some(val) = self.iterator.lock().expect(EXPECT_MUTEX).next() {
-        self.backtracking_vec.write().expect(EXPECT_RW).push(val.clone());
-        self.position = self.position + 1;
-        Some(val)
-      } else {
-        None
-      }
-    } else {
-      let old_position = self.position;
-      self.position = self.position + 1;
-      Some(self.backtracking_vec.read().expect(EXPECT_RW)[old_position].clone())
-    }
+    let result = self.try_next();
+    next_or_recover(result, self.policy)
   }
 }
 
@@ -162,15 +612,106 @@ impl<Iter> BacktrackingIterator for ConcurrentCopyingBacktrackingIterator<Iter>
   }
 
   fn get_oldest_point(&self) -> usize {
-    // Always the oldest position
-    0_usize
+    self.hold
   }
 
   fn backtrack(&mut self, position: usize) {
+    if position >= self.hold && position != self.position {
+      trace_event!(slot = self.slot, from = self.position, to = position, history_len = self.backtracking_vec.len(), "backtrack");
+      if position < self.backtracking_vec.len() {
+        trace_event!(slot = self.slot, point = position, frontier = self.backtracking_vec.len(), "replay started");
+        self.replay.open(position, self.backtracking_vec.len());
+      }
+      self.position = position;
+      AtomicStats::count(&self.stats.backtracks);
+    }
+  }
+
+  fn undo_peek(&mut self, position: usize) {
     self.position = position;
   }
 }
 
+impl<Iter> Record for ConcurrentCopyingBacktrackingIterator<Iter> where Iter: Iterator, Iter::Item: Clone {
+  type RefPoint = usize;
+
+  fn get_ref_point(&self) -> usize {
+    self.position
+  }
+
+  /// Stop this clone from backtracking before the given point, and free any history
+  /// which no other clone still holds.
+  fn forget_before(&mut self, point: usize) {
+    if self.hold <= point && point <= self.position {
+      debug_event!(slot = self.slot, before = point, items = point - self.hold, history_len = self.backtracking_vec.len(), "forget");
+      self.hold = point;
+      AtomicStats::count(&self.stats.forgets);
+      let mut registry = Registry::lock(&self.registry);
+      registry.holds[self.slot] = Some(point);
+      self.reclaim(&registry);
+    }
+  }
+}
+// SHARED VERSION
+
+use self::items::ArcItems;
+
+mod items {
+  use std::sync::Arc;
+
+  /// A wrapper around a source which puts each item in an `Arc`, so that replaying an
+  /// item only clones its handle.
+  pub struct ArcItems<Iter> {
+    iter: Iter,
+  }
+
+  impl<Iter> ArcItems<Iter> where Iter: Iterator {
+    pub fn new(iter: Iter) -> Self {
+      ArcItems { iter }
+    }
+  }
+
+  impl<Iter> Iterator for ArcItems<Iter> where Iter: Iterator {
+    type Item = Arc<Iter::Item>;
+
+    fn next(&mut self) -> Option<Arc<Iter::Item>> {
+      self.iter.next().map(Arc::new)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+      self.iter.size_hint()
+    }
+  }
+}
+
+impl<Iter> ConcurrentCopyingBacktrackingIterator<ArcItems<Iter>> where Iter: Iterator {
+  /// Create an iterator which yields shared handles to the items of an existing
+  /// iterator. Every clone replays the same items, which don't need to be `Clone`, and
+  /// the handles can be kept after every clone has gone.
+  ///
+  /// This is the concurrent version of `BacktrackingRecorder::shared`, which uses
+  /// `Rc` instead.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::concurrent::ConcurrentCopyingBacktrackingIterator;
+  /// use std::sync::Arc;
+  ///
+  /// // Not `Clone`
+  /// struct Node(u32);
+  ///
+  /// let mut bt = ConcurrentCopyingBacktrackingIterator::shared((1..=3).map(Node));
+  /// let mut worker = bt.clone();
+  /// let sum = std::thread::spawn(move || worker.by_ref().map(|node| node.0).sum::<u32>());
+  /// assert!(sum.join().unwrap() == 6);
+  ///
+  /// let first = bt.next().unwrap();
+  /// drop(bt);
+  /// assert!(first.0 == 1 && Arc::strong_count(&first) == 1);
+  /// ```
+  pub fn shared(iterator: Iter) -> Self {
+    ConcurrentCopyingBacktrackingIterator::from(ArcItems::new(iterator))
+  }
+}
 
 #[cfg(test)]
 mod tests {
@@ -202,11 +743,97 @@ mod tests {
     }
   }
   
+  #[test]
+  fn many_thread_test() {
+    let bt_con_iter = crate::concurrent::ConcurrentCopyingBacktrackingIterator::from(0..10_000);
+
+    let workers: Vec<_> = (0..8).map(|_| {
+      let bt_iter = bt_con_iter.clone();
+      std::thread::spawn(move || bt_iter.eq(0..10_000))
+    }).collect();
+
+    for worker in workers {
+      assert!(worker.join().unwrap());
+    }
+  }
+
+  #[test]
+  fn continue_with_recorded_test() {
+    use crate::concurrent::RecoveryPolicy;
+
+    let source = (1_u8..=3).map(|i| if i == 2 { panic!("Bad item!") } else { i });
+    let mut bt_iter = crate::concurrent::ConcurrentReferencingBacktrackingIterator::from(source)
+      .with_recovery_policy(RecoveryPolicy::ContinueWithRecorded);
+    assert!(bt_iter.next() == Some(&1_u8));
+
+    let mut worker = bt_iter.clone();
+    assert!(std::thread::spawn(move || worker.next().is_some()).join().is_err());
+
+    assert!(bt_iter.try_next() == Ok(None));
+    assert!(bt_iter.next().is_none());
+  }
+
+  #[test]
+  fn reclaim_test() {
+    use crate::{BacktrackingIterator, Record};
+
+    let mut fast = crate::concurrent::ConcurrentCopyingBacktrackingIterator::from(1..1000);
+    let slow = fast.clone();
+
+    for _ in 1..500 {
+      fast.next();
+    }
+    fast.forget();
+    assert!(fast.backtracking_vec.reclaimed() == 0);
+
+    // Once the slow clone is gone, nothing holds the start of the history, and
+    // every whole segment before the fast clone is freed
+    drop(slow);
+    assert!(fast.backtracking_vec.reclaimed() == 480);
+    fast.start_again();
+    assert!(fast.next() == Some(500));
+  }
+
+  #[test]
+  fn referencing_forget_keeps_history_test() {
+    use crate::{BacktrackingIterator, Record};
+
+    let mut bt = crate::concurrent::ConcurrentReferencingBacktrackingIterator::from(1..100);
+    let first = bt.next().unwrap();
+    for _ in 1..50 {
+      bt.next();
+    }
+    bt.forget();
+
+    // The yielded references are still valid, so nothing is freed
+    assert!(bt.backtracking_vec.reclaimed() == 0);
+    assert!(bt.get_oldest_point() == 50);
+    assert!(*first == 1);
+  }
+
+  #[test]
+  fn stats_across_clones_test() {
+    use crate::BacktrackingIterator;
+
+    let mut first = crate::concurrent::ConcurrentCopyingBacktrackingIterator::from(1..10);
+    let mut second = first.clone();
+    first.next();
+    first.peek();
+    second.next();
+    second.backtrack(0);
+    second.backtrack(0);
+
+    // A dropped clone's counts are kept
+    drop(second);
+    let stats = first.stats();
+    assert!(stats.pulled == 2 && stats.replayed == 1 && stats.backtracks == 1);
+  }
+
   #[test]
   fn dont_need_clone_test() {
     use matches::{matches};
 
-    struct Uncloneable {};
+    struct Uncloneable {}
     let uncloneables = vec![Uncloneable {}];
     let mut bt_con_iter = crate::concurrent::ConcurrentReferencingBacktrackingIterator::from(uncloneables.into_iter());
 
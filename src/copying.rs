@@ -19,13 +19,16 @@
  * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
-use super::BacktrackingRecorder;
+use alloc::borrow::Cow;
+use alloc::string::String;
+
+use super::{BacktrackingRecorder, HistoryShift, Stats};
 use super::BacktrackingState::*;
 
 /// An iterator over a historical record which produces memory clones of historical
 /// elements
 pub struct CopyingBacktrackingIterator<'record, I> where I: Iterator, I::Item: Clone {
-  recorder: &'record mut BacktrackingRecorder<I>,
+  pub(crate) recorder: &'record mut BacktrackingRecorder<I>,
 }
 
 /// In order to be able to backtrack, the iterator values must be `Clone`able
@@ -40,19 +43,25 @@ impl<'record, I> Iterator for CopyingBacktrackingIterator<'record, I> where I: I
       Progressing => {
         if let Some(val) = self.recorder.iterator.next() {
           self.recorder.backtracking_vec.push(val.clone());
+          self.recorder.count_pull();
+          let frontier = self.recorder.frontier();
+          self.recorder.reached(frontier);
           Some(val)
         } else {
+          debug_event!(frontier = self.recorder.frontier(), "source exhausted");
           None
         }
       },
       Backtracking { position } => {
-        if position >= self.recorder.backtracking_vec.len() {
-          self.recorder.state = Progressing;
+        if position >= self.recorder.frontier() {
+          self.recorder.reached_frontier();
           self.next()
         } else {
-          let backtracked_value = self.recorder.backtracking_vec[position].clone();
+          let backtracked_value = self.recorder.backtracking_vec[position - self.recorder.forgotten].clone();
           let new_position = position + 1;
           self.recorder.state = Backtracking { position: new_position };
+          self.recorder.reached(new_position);
+          self.recorder.stats.replayed += 1;
           Some(backtracked_value)
         }
       },
@@ -66,6 +75,60 @@ impl<'record, I> CopyingBacktrackingIterator<'record, I> where I:Iterator, I::It
       recorder,
     }
   }
+
+  /// The furthest point reached - see `BacktrackingRecorder::furthest_point`.
+  pub fn furthest_point(&self) -> usize {
+    self.recorder.furthest_point()
+  }
+
+  /// Register that `expected` would have matched at `point` - see
+  /// `BacktrackingRecorder::expect`.
+  pub fn expect(&mut self, point: usize, expected: impl Into<Cow<'static, str>>) {
+    self.recorder.expect(point, expected)
+  }
+
+  /// How the recorder has been used - see `BacktrackingRecorder::stats`.
+  pub fn stats(&self) -> Stats {
+    self.recorder.stats()
+  }
+
+  /// Put the given items into the stream at the current point - see
+  /// `BacktrackingRecorder::push_front`.
+  pub fn push_front(&mut self, items: impl IntoIterator<Item=I::Item>) -> HistoryShift {
+    self.recorder.push_front(items)
+  }
+
+  /// Put a single item into the stream at the current point, so that it is yielded
+  /// by the next call to `next()`.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::BacktrackingRecorder;
+  ///
+  /// let mut rec = BacktrackingRecorder::new(1_u8..=2);
+  /// let mut bt = rec.copying();
+  /// let first = bt.next().unwrap();
+  /// bt.unread(first);
+  /// assert!(bt.next().unwrap() == 1_u8);
+  /// assert!(bt.next().unwrap() == 2_u8);
+  /// ```
+  pub fn unread(&mut self, item: I::Item) {
+    self.recorder.unread(item);
+  }
+}
+
+impl<'record, I> core::fmt::Debug for CopyingBacktrackingIterator<'record, I> where I: Iterator, I::Item: core::fmt::Debug, I::Item: Clone {
+  fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    f.debug_struct("CopyingBacktrackingIterator")
+      .field("recorder", &*self.recorder)
+      .finish()
+  }
+}
+
+impl<'record, I> CopyingBacktrackingIterator<'record, I> where I: Iterator, I::Item: core::fmt::Debug, I::Item: Clone {
+  /// Render the history for debugging - see `BacktrackingRecorder::dump_history`.
+  pub fn dump_history(&self) -> String {
+    self.recorder.dump_history()
+  }
 }
 
 use super::BacktrackingIterator;
@@ -75,18 +138,22 @@ impl<'record, I> BacktrackingIterator for CopyingBacktrackingIterator<'record, I
 
   fn get_ref_point(&self) -> usize {
     match self.recorder.state {
-        Progressing => self.recorder.backtracking_vec.len(),
+        Progressing => self.recorder.frontier(),
         Backtracking { position } => position,
     }
   }
 
   fn get_oldest_point(&self) -> usize {
-    // Always the oldest position
-    0_usize
+    // Everything before this has been forgotten
+    self.recorder.forgotten
   }
 
   fn backtrack(&mut self, position: usize) {
-    self.recorder.state = Backtracking { position };
+    self.recorder.backtrack_to(position);
+  }
+
+  fn undo_peek(&mut self, position: usize) {
+    self.recorder.undo_peek(position);
   }
 }
 
@@ -112,7 +179,7 @@ pub struct CopyingWalkback<'record, I> where I: Iterator, I::Item: Clone {
 
 impl<'history, I> CopyingWalkback<'history, I> where I: Iterator, I::Item: Clone {
   fn new(backtracker: &'history CopyingBacktrackingIterator<I>) -> Self {
-    let history_len = backtracker.recorder.backtracking_vec.len();
+    let history_len = backtracker.recorder.frontier();
     CopyingWalkback {
       backtracker: &backtracker.recorder,
       reverse_position: history_len,
@@ -130,15 +197,25 @@ impl<'history, I> Walkback<'history> for CopyingWalkback<'history, I> where I: I
   }
 }
 
+impl<'history, I> core::fmt::Debug for CopyingWalkback<'history, I> where I: Iterator, I::Item: Clone + core::fmt::Debug {
+  fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    f.debug_struct("CopyingWalkback")
+      .field("position", &self.reverse_position)
+      .field("history", &self.backtracker.backtracking_vec)
+      .field("forgotten", &self.backtracker.forgotten)
+      .finish()
+  }
+}
+
 impl<'history, I> Iterator for CopyingWalkback<'history, I> where I: Iterator, I::Item: Clone {
   type Item = I::Item;
 
   fn next(&mut self) -> Option<Self::Item> {
-    if self.reverse_position == 0 {
+    if self.reverse_position == self.backtracker.forgotten {
       None
     } else {
       let new_position = self.reverse_position - 1_usize;
-      let val = &self.backtracker.backtracking_vec[new_position];
+      let val = &self.backtracker.backtracking_vec[new_position - self.backtracker.forgotten];
       self.reverse_position = new_position;
       Some(val.clone())
     }
@@ -0,0 +1,306 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use super::{BacktrackingIterator, BacktrackingRecorder, Record};
+
+/// The history shared between all the `Cursor`s over a source
+struct SharedHistory<Iter> where Iter: Iterator {
+  iterator: Iter,
+  backtracking_vec: Vec<Iter::Item>,
+  forgotten: usize,
+  /// The position of every live cursor, indexed by the cursor's slot
+  positions: Vec<Option<usize>>,
+}
+
+impl<Iter> SharedHistory<Iter> where Iter: Iterator {
+  fn frontier(&self) -> usize {
+    self.forgotten + self.backtracking_vec.len()
+  }
+
+  fn register(&mut self, position: usize) -> usize {
+    if let Some(slot) = self.positions.iter().position(Option::is_none) {
+      self.positions[slot] = Some(position);
+      slot
+    } else {
+      self.positions.push(Some(position));
+      self.positions.len() - 1
+    }
+  }
+}
+
+/// A backtracking iterator over a history which is shared with every clone of the
+/// cursor. Each clone has its own position in the history, and items pulled from the
+/// source by any clone can be replayed by all the others.
+///
+/// Cursors are cheap to clone, but can't be sent between threads - see the
+/// `concurrent` module for that.
+/// ```
+/// extern crate backtracking_iterator;
+/// use backtracking_iterator::{BacktrackingIterator, Cursor};
+///
+/// let mut first = Cursor::from(1_u8..=3);
+/// first.next(); // 1_u8
+///
+/// let mut second = first.clone();
+/// assert!(first.next().unwrap() == 2_u8);
+/// assert!(first.next().unwrap() == 3_u8);
+///
+/// // The second cursor kept its own position
+/// assert!(second.next().unwrap() == 2_u8);
+/// second.start_again();
+/// assert!(second.next().unwrap() == 1_u8);
+/// ```
+pub struct Cursor<Iter> where Iter: Iterator {
+  history: Rc<RefCell<SharedHistory<Iter>>>,
+  slot: usize,
+  /// Whether the cursor backtracked and hasn't caught up with the frontier since
+  replaying: bool,
+}
+
+impl<Iter> From<Iter> for Cursor<Iter> where Iter: Iterator {
+  /// Create a `Cursor` from an existing iterator.
+  fn from(iterator: Iter) -> Self {
+    Cursor::new(SharedHistory {
+      iterator,
+      backtracking_vec: vec![],
+      forgotten: 0,
+      positions: vec![],
+    }, 0)
+  }
+}
+
+impl<Iter> Cursor<Iter> where Iter: Iterator {
+  fn new(mut history: SharedHistory<Iter>, position: usize) -> Self {
+    let slot = history.register(position);
+    Cursor {
+      history: Rc::new(RefCell::new(history)),
+      slot,
+      replaying: false,
+    }
+  }
+
+  fn position(&self) -> usize {
+    self.history.borrow().positions[self.slot].expect("A live cursor must have a position")
+  }
+
+  fn set_position(&self, position: usize) {
+    self.history.borrow_mut().positions[self.slot] = Some(position);
+  }
+}
+
+impl<Iter> core::fmt::Debug for Cursor<Iter> where Iter: Iterator, Iter::Item: core::fmt::Debug {
+  /// Show this cursor's position and the shared history. The source iterator is not
+  /// shown.
+  fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    let history = self.history.borrow();
+    f.debug_struct("Cursor")
+      .field("position", &history.positions[self.slot])
+      .field("history", &history.backtracking_vec)
+      .field("forgotten", &history.forgotten)
+      .finish_non_exhaustive()
+  }
+}
+
+impl<Iter> Cursor<Iter> where Iter: Iterator, Iter::Item: core::fmt::Debug {
+  /// Render the shared history for debugging, with this cursor's position marked -
+  /// see `BacktrackingRecorder::dump_history`.
+  pub fn dump_history(&self) -> String {
+    let history = self.history.borrow();
+    crate::dump::dump_history(&history.backtracking_vec, history.forgotten, self.position(), history.frontier())
+  }
+}
+
+impl<Iter> BacktrackingRecorder<Iter> where Iter: Iterator {
+  /// Turn the recorder into a `Cursor`, keeping its history and its position in the
+  /// history.
+  pub fn into_cursor(self) -> Cursor<Iter> {
+    let position = self.get_ref_point();
+    Cursor::new(SharedHistory {
+      iterator: self.iterator,
+      backtracking_vec: self.backtracking_vec,
+      forgotten: self.forgotten,
+      positions: vec![],
+    }, position)
+  }
+}
+
+impl<Iter> Clone for Cursor<Iter> where Iter: Iterator {
+  /// Create a new cursor at the same position in the shared history
+  fn clone(&self) -> Self {
+    let position = self.position();
+    let slot = self.history.borrow_mut().register(position);
+    Cursor {
+      history: self.history.clone(),
+      slot,
+      replaying: self.replaying,
+    }
+  }
+}
+
+impl<Iter> Drop for Cursor<Iter> where Iter: Iterator {
+  fn drop(&mut self) {
+    self.history.borrow_mut().positions[self.slot] = None;
+  }
+}
+
+impl<Iter> Iterator for Cursor<Iter> where Iter: Iterator, Iter::Item: Clone {
+  type Item = Iter::Item;
+
+  fn next(&mut self) -> Option<Iter::Item> {
+    let mut history = self.history.borrow_mut();
+    let position = history.positions[self.slot].expect("A live cursor must have a position");
+
+    let value = if position >= history.frontier() {
+      if self.replaying {
+        trace_event!(point = history.frontier(), history_len = history.backtracking_vec.len(), "frontier reached");
+        self.replaying = false;
+      }
+      let val = match history.iterator.next() {
+        Some(val) => val,
+        None => {
+          debug_event!(frontier = history.frontier(), "source exhausted");
+          return None;
+        },
+      };
+      history.backtracking_vec.push(val.clone());
+      val
+    } else {
+      history.backtracking_vec[position - history.forgotten].clone()
+    };
+
+    history.positions[self.slot] = Some(position + 1);
+    Some(value)
+  }
+}
+
+impl<Iter> BacktrackingIterator for Cursor<Iter> where Iter: Iterator, Iter::Item: Clone {
+  type RefPoint = usize;
+
+  fn get_ref_point(&self) -> usize {
+    self.position()
+  }
+
+  fn get_oldest_point(&self) -> usize {
+    self.history.borrow().forgotten
+  }
+
+  fn backtrack(&mut self, position: usize) {
+    let (oldest, frontier, current) = {
+      let history = self.history.borrow();
+      let current = history.positions[self.slot].expect("A live cursor must have a position");
+      (history.forgotten, history.frontier(), current)
+    };
+
+    if oldest <= position && position <= frontier && position != current {
+      trace_event!(from = current, to = position, history_len = frontier - oldest, "backtrack");
+      if position < frontier {
+        trace_event!(point = position, frontier = frontier, "replay started");
+        self.replaying = true;
+      }
+      self.set_position(position);
+    }
+  }
+
+  /// Move back to where the cursor was before a peek. This isn't traced as a
+  /// backtrack.
+  fn undo_peek(&mut self, position: usize) {
+    self.set_position(position);
+  }
+}
+
+impl<Iter> Record for Cursor<Iter> where Iter: Iterator {
+  type RefPoint = usize;
+
+  fn get_ref_point(&self) -> usize {
+    self.position()
+  }
+
+  /// Forget the history before the given point, or before the slowest live cursor
+  /// if it is further back.
+  fn forget_before(&mut self, point: usize) {
+    let mut history = self.history.borrow_mut();
+    let slowest = history.positions.iter().filter_map(|&position| position).min().unwrap_or(point);
+    let point = core::cmp::min(point, slowest);
+
+    if history.forgotten <= point && point <= history.frontier() {
+      let forgotten = history.forgotten;
+      history.backtracking_vec.drain(..point - forgotten);
+      debug_event!(before = point, items = point - forgotten, history_len = history.backtracking_vec.len(), "forget");
+      history.forgotten = point;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{BacktrackingIterator, Cursor, Record};
+
+  #[test]
+  fn forget_respects_other_cursors_test() {
+    let mut fast = Cursor::from(1_u8..=6);
+    fast.next();
+    let mut slow = fast.clone();
+
+    for _ in 0..4 {
+      fast.next();
+    }
+
+    fast.forget();
+    assert!(fast.get_oldest_point() == 1);
+    assert!(slow.next().unwrap() == 2_u8);
+
+    drop(slow);
+    fast.forget();
+    assert!(fast.get_oldest_point() == 5);
+    assert!(fast.next().unwrap() == 6_u8);
+  }
+
+  #[test]
+  fn shared_pull_test() {
+    let mut first = Cursor::from(1_u8..=3);
+    let mut second = first.clone();
+
+    assert!(first.next().unwrap() == 1_u8);
+    assert!(second.next().unwrap() == 1_u8);
+    assert!(second.next().unwrap() == 2_u8);
+    assert!(first.next().unwrap() == 2_u8);
+    assert!(first.next().unwrap() == 3_u8);
+    assert!(first.next().is_none());
+    assert!(second.next().unwrap() == 3_u8);
+  }
+
+  #[test]
+  fn backtrack_beyond_frontier_test() {
+    let mut cursor = Cursor::from(1_u8..=3);
+    cursor.next();
+
+    cursor.backtrack(2);
+    assert!(BacktrackingIterator::get_ref_point(&cursor) == 1);
+    assert!(cursor.next().unwrap() == 2_u8);
+    assert!(BacktrackingIterator::get_ref_point(&cursor) == 2);
+  }
+}
@@ -0,0 +1,56 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use alloc::string::{String, ToString};
+use core::fmt::{Debug, Write};
+
+/// Render a history for debugging, one item per line with its `RefPoint`. The line
+/// for the cursor's point is marked with `>`, and the forgotten items and the frontier
+/// are shown before and after the items. A cursor at or past the frontier is marked on
+/// the frontier's line.
+pub(crate) fn dump_history<'history, T>(items: impl IntoIterator<Item=&'history T>, forgotten: usize, cursor: usize, frontier: usize) -> String
+  where T: Debug + 'history {
+  let width = frontier.to_string().len();
+  let marker = |point: usize| if point == cursor || (point == frontier && cursor > frontier) { '>' } else { ' ' };
+
+  let mut dump = String::new();
+  if forgotten > 0 {
+    // Writing to a `String` never fails
+    let _ = writeln!(dump, "  {:>width$}  ({} forgotten)", "..", forgotten, width = width);
+  }
+  for (point, item) in (forgotten..).zip(items) {
+    let _ = writeln!(dump, "{} {:>width$}: {:?}", marker(point), point, item, width = width);
+  }
+  let _ = writeln!(dump, "{} {:>width$}: (frontier)", marker(frontier), frontier, width = width);
+  dump
+}
+
+#[cfg(test)]
+mod tests {
+  use super::dump_history;
+
+  #[test]
+  fn markers_test() {
+    let items = vec!['c', 'd'];
+    assert!(dump_history(&items, 8, 9, 10) == "  ..  (8 forgotten)\n   8: 'c'\n>  9: 'd'\n  10: (frontier)\n");
+    assert!(dump_history(&items, 0, 5, 2) == "  0: 'c'\n  1: 'd'\n> 2: (frontier)\n");
+  }
+}
@@ -0,0 +1,227 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// The error at the furthest point a parser got to: where it was, and everything
+/// which would have let it carry on from there. `reached` is how far the parser read,
+/// which may be past `point` if it read on without expecting anything further in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FurthestFailure<RefPoint, E> {
+  pub point: RefPoint,
+  pub expected: Vec<E>,
+  pub reached: RefPoint,
+}
+
+impl<RefPoint, E> fmt::Display for FurthestFailure<RefPoint, E> where RefPoint: fmt::Display, E: fmt::Display {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "at {}: expected ", self.point)?;
+    match self.expected.split_last() {
+      None => write!(f, "nothing"),
+      Some((last, [])) => write!(f, "{}", last),
+      Some((last, rest)) => {
+        for (index, expected) in rest.iter().enumerate() {
+          if index > 0 {
+            write!(f, ", ")?;
+          }
+          write!(f, "{}", expected)?;
+        }
+        write!(f, " or {}", last)
+      },
+    }
+  }
+}
+
+/// A record of what a parser expected to see, kept only for the furthest point
+/// where anything was expected. Registering an expectation further on throws away
+/// the ones before it, and registering one at an earlier point does nothing - so
+/// backtracking out of a failed alternative never loses how far it got.
+///
+/// A `BacktrackingRecorder` keeps one of these for its own history - see
+/// `BacktrackingRecorder::expect`. This type is for other backtracking iterators,
+/// and for expectations which aren't descriptions in strings.
+/// ```
+/// extern crate backtracking_iterator;
+/// use backtracking_iterator::{Expectations, FurthestFailure};
+///
+/// let mut expectations = Expectations::new();
+/// expectations.expect(1, "digit");
+/// expectations.expect(3, "')'");
+/// expectations.expect(3, "'+'");
+/// expectations.expect(2, "letter");
+///
+/// let failure = expectations.into_failure().unwrap();
+/// assert!(failure == FurthestFailure { point: 3, expected: vec!["')'", "'+'"], reached: 3 });
+/// assert!(failure.to_string() == "at 3: expected ')' or '+'");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Expectations<RefPoint, E> {
+  furthest: Option<FurthestFailure<RefPoint, E>>,
+}
+
+impl<RefPoint, E> Default for Expectations<RefPoint, E> where RefPoint: Ord + Clone, E: PartialEq {
+  fn default() -> Self {
+    Expectations::new()
+  }
+}
+
+impl<RefPoint, E> Expectations<RefPoint, E> where RefPoint: Ord + Clone, E: PartialEq {
+  /// Create a tracker with nothing expected
+  pub fn new() -> Self {
+    Expectations {
+      furthest: None,
+    }
+  }
+
+  /// Register that `expected` would have matched at `point`. Expectations already
+  /// registered at the same point are not repeated.
+  pub fn expect(&mut self, point: RefPoint, expected: E) {
+    match self.furthest {
+      Some(ref mut failure) if failure.point == point => {
+        if !failure.expected.contains(&expected) {
+          failure.expected.push(expected);
+        }
+      },
+      Some(ref failure) if failure.point > point => {},
+      _ => {
+        self.furthest = Some(FurthestFailure { point: point.clone(), expected: vec![expected], reached: point });
+      },
+    }
+  }
+
+  /// The furthest point anything was expected at
+  pub fn furthest_point(&self) -> Option<&RefPoint> {
+    self.furthest.as_ref().map(|failure| &failure.point)
+  }
+
+  /// The merged failure at the furthest point, if anything was expected
+  pub fn failure(&self) -> Option<&FurthestFailure<RefPoint, E>> {
+    self.furthest.as_ref()
+  }
+
+  /// Take the merged failure at the furthest point, if anything was expected
+  pub fn into_failure(self) -> Option<FurthestFailure<RefPoint, E>> {
+    self.furthest
+  }
+
+  /// Produce the failure for a parse which read up to `reached` - usually the
+  /// recorder's `furthest_point()`. The failure keeps the expectations at the
+  /// furthest point anything was expected, even if the parse read past it. If
+  /// nothing was expected, the failure is at `reached`, with nothing expected.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::{BacktrackingIterator, BacktrackingRecorder, Expectations};
+  ///
+  /// let mut rec = BacktrackingRecorder::new("ab".chars());
+  /// let mut expectations = Expectations::new();
+  /// {
+  ///   let mut bt = rec.copying();
+  ///   bt.next();
+  ///   bt.next();
+  ///   expectations.expect(1, 'c');
+  ///   bt.start_again();
+  /// }
+  ///
+  /// let failure = expectations.failure_at(rec.furthest_point());
+  /// assert!(failure.point == 1 && failure.expected == vec!['c']);
+  /// assert!(failure.reached == 2);
+  /// ```
+  pub fn failure_at(self, reached: RefPoint) -> FurthestFailure<RefPoint, E> {
+    match self.furthest {
+      Some(mut failure) => {
+        if reached > failure.reached {
+          failure.reached = reached;
+        }
+        failure
+      },
+      None => FurthestFailure { point: reached.clone(), expected: vec![], reached },
+    }
+  }
+
+  /// Forget everything expected
+  pub fn clear(&mut self) {
+    self.furthest = None;
+  }
+}
+
+impl<E> Expectations<usize, E> {
+  /// Move the furthest point to follow a change to the history
+  pub(crate) fn map_point(&mut self, map: impl Fn(usize) -> usize) {
+    if let Some(ref mut failure) = self.furthest {
+      failure.point = map(failure.point);
+      failure.reached = map(failure.reached);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{BacktrackingIterator, BacktrackingRecorder};
+
+  #[test]
+  fn furthest_survives_backtrack_test() {
+    let mut rec = BacktrackingRecorder::new("abcd".chars());
+    {
+      let mut bt = rec.copying();
+      // First alternative: "abx"
+      bt.next();
+      bt.next();
+      let point = bt.get_ref_point();
+      if bt.next() != Some('x') {
+        bt.expect(point, "'x'");
+      }
+      bt.start_again();
+      // Second alternative: "ay"
+      bt.next();
+      let point = bt.get_ref_point();
+      if bt.next() != Some('y') {
+        bt.expect(point, "'y'");
+      }
+      assert!(bt.furthest_point() == 3);
+    }
+
+    assert!(rec.furthest_point() == 3);
+    let failure = rec.failure();
+    assert!(failure.point == 2 && failure.expected == vec!["'x'"]);
+    assert!(failure.reached == 3);
+  }
+
+  #[test]
+  fn nothing_expected_test() {
+    let mut rec = BacktrackingRecorder::new("ab".chars());
+    rec.copying().next();
+    let failure = rec.failure();
+    assert!(failure.point == 1 && failure.expected.is_empty() && failure.reached == 1);
+  }
+
+  #[test]
+  fn push_front_moves_furthest_test() {
+    let mut rec = BacktrackingRecorder::new(1_u8..=3);
+    rec.copying().by_ref().take(2).for_each(drop);
+    rec.expect(2, "3");
+    rec.copying().backtrack(1);
+    rec.unread(0);
+    assert!(rec.furthest_point() == 3);
+    assert!(rec.failure().point == 3);
+  }
+}
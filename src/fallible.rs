@@ -0,0 +1,319 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use super::{BacktrackingIterator, BacktrackingState, Record};
+use self::BacktrackingState::{Progressing, Backtracking};
+
+/// A recorder for a source of `Result`s, such as a reader, which only records the
+/// `Ok` items. An error is yielded once, at the point where it happened, and is not
+/// recorded - so backtracking replays only the successful items, and reading at the
+/// frontier again polls the source again, which retries after a transient failure.
+/// ```
+/// extern crate backtracking_iterator;
+/// use backtracking_iterator::{BacktrackingIterator, FallibleRecorder};
+///
+/// let source = vec![Ok(1_u8), Err("timed out"), Ok(2_u8)];
+/// let mut rec = FallibleRecorder::new(source.into_iter());
+/// let mut bt = rec.copying();
+/// assert!(bt.next() == Some(Ok(1_u8)));
+/// assert!(bt.next() == Some(Err("timed out")));
+/// // The error didn't move the cursor
+/// assert!(bt.get_ref_point() == 1);
+/// assert!(bt.next() == Some(Ok(2_u8)));
+///
+/// bt.start_again();
+/// assert!(bt.collect::<Vec<_>>() == vec![Ok(1_u8), Ok(2_u8)]);
+/// ```
+pub struct FallibleRecorder<Iter, T, E> where Iter: Iterator<Item=Result<T, E>> {
+  iterator: Iter,
+  /// Each item is boxed, so that references handed out by a referencing iterator stay
+  /// valid when the history grows
+  backtracking_vec: Vec<Box<T>>,
+  /// The number of items which have been forgotten from the front of the history
+  forgotten: usize,
+  state: BacktrackingState,
+  error_marker: PhantomData<E>,
+}
+
+impl<Iter, T, E> FallibleRecorder<Iter, T, E> where Iter: Iterator<Item=Result<T, E>> {
+  /// Create a `FallibleRecorder` from an existing iterator.
+  pub fn new(iterator: Iter) -> Self {
+    FallibleRecorder {
+      iterator,
+      backtracking_vec: vec![],
+      forgotten: 0,
+      state: Progressing,
+      error_marker: PhantomData,
+    }
+  }
+
+  pub fn copying(&mut self) -> FallibleCopyingIterator<'_, Iter, T, E> where T: Clone {
+    FallibleCopyingIterator { recorder: self }
+  }
+
+  pub fn referencing(&mut self) -> FallibleReferencingIterator<'_, Iter, T, E> {
+    FallibleReferencingIterator { recorder: self }
+  }
+
+  /// The `RefPoint` one past the newest item in the history
+  fn frontier(&self) -> usize {
+    self.forgotten + self.backtracking_vec.len()
+  }
+
+  /// Move the cursor on, and get the point of the item it moved past, or the error
+  /// that the source produced instead of an item. Errors leave the cursor where it is.
+  fn advance(&mut self) -> Option<Result<usize, E>> {
+    if let Backtracking { position } = self.state {
+      if position < self.frontier() {
+        self.state = Backtracking { position: position + 1 };
+        return Some(Ok(position));
+      }
+      trace_event!(point = self.frontier(), history_len = self.backtracking_vec.len(), "frontier reached");
+      self.state = Progressing;
+    }
+
+    match self.iterator.next() {
+      Some(Ok(val)) => {
+        self.backtracking_vec.push(Box::new(val));
+        Some(Ok(self.frontier() - 1))
+      },
+      Some(Err(error)) => {
+        debug_event!(frontier = self.frontier(), "source error");
+        Some(Err(error))
+      },
+      None => {
+        debug_event!(frontier = self.frontier(), "source exhausted");
+        None
+      },
+    }
+  }
+
+  fn get(&self, point: usize) -> &T {
+    &self.backtracking_vec[point - self.forgotten]
+  }
+
+  fn get_oldest_point(&self) -> usize {
+    self.forgotten
+  }
+
+  /// Move the cursor back to the given point, if it is remembered
+  fn backtrack(&mut self, position: usize) {
+    if position >= self.forgotten && position != self.get_ref_point() {
+      trace_event!(from = self.get_ref_point(), to = position, history_len = self.backtracking_vec.len(), "backtrack");
+      if position < self.frontier() {
+        trace_event!(point = position, frontier = self.frontier(), "replay started");
+      }
+      self.state = Backtracking { position };
+    }
+  }
+
+  /// Move the cursor back to where it was before a peek. This isn't traced as a
+  /// backtrack.
+  fn undo_peek(&mut self, position: usize) {
+    self.state = Backtracking { position };
+  }
+}
+
+impl<Iter, T, E> fmt::Debug for FallibleRecorder<Iter, T, E> where Iter: Iterator<Item=Result<T, E>>, T: fmt::Debug {
+  /// Show the recorded history and the cursor. The source iterator is not shown.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("FallibleRecorder")
+      .field("history", &self.backtracking_vec)
+      .field("forgotten", &self.forgotten)
+      .field("state", &self.state)
+      .finish_non_exhaustive()
+  }
+}
+
+impl<Iter, T, E> FallibleRecorder<Iter, T, E> where Iter: Iterator<Item=Result<T, E>>, T: fmt::Debug {
+  /// Render the remembered history for debugging - see
+  /// `BacktrackingRecorder::dump_history`.
+  pub fn dump_history(&self) -> String {
+    crate::dump::dump_history(&self.backtracking_vec, self.forgotten, self.get_ref_point(), self.frontier())
+  }
+}
+
+impl<Iter, T, E> Record for FallibleRecorder<Iter, T, E> where Iter: Iterator<Item=Result<T, E>> {
+  type RefPoint = usize;
+
+  fn get_ref_point(&self) -> usize {
+    match self.state {
+      Progressing => self.frontier(),
+      Backtracking { position } => position,
+    }
+  }
+
+  fn forget_before(&mut self, position: usize) {
+    if self.forgotten <= position && position <= self.frontier() {
+      debug_event!(before = position, items = position - self.forgotten, history_len = self.frontier() - position, "forget");
+      self.backtracking_vec.drain(..position - self.forgotten);
+      self.forgotten = position;
+
+      if let Backtracking { position: current } = self.state {
+        if current < position {
+          self.state = Backtracking { position };
+        }
+      }
+    }
+  }
+
+  fn forget(&mut self) {
+    let frontier = self.frontier();
+    self.forget_before(frontier);
+    self.state = Progressing;
+  }
+}
+
+/// An iterator over a `FallibleRecorder` which produces clones of the recorded items,
+/// along with any errors from the source
+pub struct FallibleCopyingIterator<'record, Iter, T, E> where Iter: Iterator<Item=Result<T, E>>, T: Clone {
+  recorder: &'record mut FallibleRecorder<Iter, T, E>,
+}
+
+impl<'record, Iter, T, E> Iterator for FallibleCopyingIterator<'record, Iter, T, E> where Iter: Iterator<Item=Result<T, E>>, T: Clone {
+  type Item = Result<T, E>;
+
+  fn next(&mut self) -> Option<Result<T, E>> {
+    let point = self.recorder.advance()?;
+    Some(point.map(|point| self.recorder.get(point).clone()))
+  }
+}
+
+impl<'record, Iter, T, E> BacktrackingIterator for FallibleCopyingIterator<'record, Iter, T, E> where Iter: Iterator<Item=Result<T, E>>, T: Clone {
+  type RefPoint = usize;
+
+  fn get_ref_point(&self) -> usize {
+    self.recorder.get_ref_point()
+  }
+
+  fn get_oldest_point(&self) -> usize {
+    self.recorder.get_oldest_point()
+  }
+
+  fn backtrack(&mut self, position: usize) {
+    self.recorder.backtrack(position);
+  }
+
+  fn undo_peek(&mut self, position: usize) {
+    self.recorder.undo_peek(position);
+  }
+}
+
+impl<'record, Iter, T, E> fmt::Debug for FallibleCopyingIterator<'record, Iter, T, E> where Iter: Iterator<Item=Result<T, E>>, T: Clone + fmt::Debug {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("FallibleCopyingIterator")
+      .field("recorder", &*self.recorder)
+      .finish()
+  }
+}
+
+/// An iterator over a `FallibleRecorder` which produces references to the recorded
+/// items, along with any errors from the source
+pub struct FallibleReferencingIterator<'record, Iter, T, E> where Iter: Iterator<Item=Result<T, E>> {
+  recorder: &'record mut FallibleRecorder<Iter, T, E>,
+}
+
+impl<'record, Iter, T, E> Iterator for FallibleReferencingIterator<'record, Iter, T, E> where Iter: Iterator<Item=Result<T, E>>, T: 'record {
+  type Item = Result<&'record T, E>;
+
+  fn next(&mut self) -> Option<Result<&'record T, E>> {
+    let point = self.recorder.advance()?;
+    Some(point.map(|point| {
+      // Items are only removed from the history by forgetting, which needs a mutable
+      // borrow on the recorder - and we hold that for the whole of 'record. Each item
+      // is in its own box, so it doesn't move when the history reallocates.
+      unsafe { &*(self.recorder.get(point) as *const T) }
+    }))
+  }
+}
+
+impl<'record, Iter, T, E> BacktrackingIterator for FallibleReferencingIterator<'record, Iter, T, E> where Iter: Iterator<Item=Result<T, E>>, T: 'record {
+  type RefPoint = usize;
+
+  fn get_ref_point(&self) -> usize {
+    self.recorder.get_ref_point()
+  }
+
+  fn get_oldest_point(&self) -> usize {
+    self.recorder.get_oldest_point()
+  }
+
+  fn backtrack(&mut self, position: usize) {
+    self.recorder.backtrack(position);
+  }
+
+  fn undo_peek(&mut self, position: usize) {
+    self.recorder.undo_peek(position);
+  }
+}
+
+impl<'record, Iter, T, E> fmt::Debug for FallibleReferencingIterator<'record, Iter, T, E> where Iter: Iterator<Item=Result<T, E>>, T: fmt::Debug {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("FallibleReferencingIterator")
+      .field("recorder", &*self.recorder)
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{BacktrackingIterator, Record};
+  use super::FallibleRecorder;
+
+  #[test]
+  fn error_is_not_replayed_test() {
+    let source = vec![Ok('a'), Err(1), Err(2), Ok('b')];
+    let mut rec = FallibleRecorder::new(source.into_iter());
+    {
+      let mut bt = rec.referencing();
+      assert!(bt.next() == Some(Ok(&'a')));
+      assert!(bt.next() == Some(Err(1)));
+      bt.start_again();
+      // Replaying reaches the frontier, and then polls the source again
+      assert!(bt.next() == Some(Ok(&'a')));
+      assert!(bt.next() == Some(Err(2)));
+      assert!(bt.next() == Some(Ok(&'b')));
+      assert!(bt.next().is_none());
+    }
+
+    rec.forget_before(1);
+    let mut bt = rec.copying();
+    bt.start_again();
+    assert!(bt.collect::<Vec<_>>() == vec![Ok('b')]);
+  }
+
+  #[test]
+  fn references_survive_growth_test() {
+    let mut rec = FallibleRecorder::new((0..1000).map(Ok::<_, ()>));
+    let mut bt = rec.referencing();
+    let first = bt.next().unwrap().unwrap();
+    // Recording more items reallocates the history
+    assert!(bt.by_ref().count() == 999);
+    assert!(*first == 0);
+  }
+}
@@ -21,17 +21,34 @@
 
 // A submodule requires this feature - it is explained later 
 #![cfg_attr(feature = "slice", feature(slice_index_methods))]
+// Without `std`, only the `alloc` collections are needed - and without `alloc`,
+// only the fixed-capacity recorder is available
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[macro_use]
+mod trace;
 
 /// Module where trait behaviour is defined
 mod traits;
 pub use self::traits::*;
 
+/// Adaptors which keep the ability to backtrack
+mod adaptors;
+pub use self::adaptors::*;
+
+mod span;
+pub use self::span::*;
+
 #[macro_use]
 mod sliceable;
 pub use self::sliceable::*;
 
 /// An internal enum for representing history
-#[derive(Clone)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) enum BacktrackingState {
   /// There may be some values in the history, but we're taking values off the iterator
   Progressing,
@@ -43,19 +60,88 @@ pub(crate) enum BacktrackingState {
   /// state and yield a value from the internal `Iterator`.
   Backtracking { position: usize },
 }
+#[cfg(feature = "alloc")]
 use crate::BacktrackingState::*;
 
+#[cfg(feature = "alloc")]
 mod record;
+#[cfg(feature = "alloc")]
 pub use self::record::*;
 
+#[cfg(feature = "alloc")]
+mod dump;
+
+mod stats;
+pub use self::stats::*;
+
+mod array;
+pub use self::array::*;
+
+#[cfg(feature = "serde")]
+mod checkpoint;
+#[cfg(feature = "serde")]
+pub use self::checkpoint::*;
+
 /// The copying backtracking iterator module
+#[cfg(feature = "alloc")]
 mod copying;
+#[cfg(feature = "alloc")]
 pub use self::copying::*;
 
+#[cfg(feature = "alloc")]
 mod referencing;
+#[cfg(feature = "alloc")]
 pub use self::referencing::*;
 
+#[cfg(feature = "alloc")]
+mod sharing;
+
+#[cfg(feature = "alloc")]
+mod fallible;
+#[cfg(feature = "alloc")]
+pub use self::fallible::*;
+
+#[cfg(feature = "alloc")]
+mod cursor;
+#[cfg(feature = "alloc")]
+pub use self::cursor::*;
+
+#[cfg(feature = "alloc")]
+mod location;
+#[cfg(feature = "alloc")]
+pub use self::location::*;
+
+#[cfg(feature = "alloc")]
+mod timeline;
+#[cfg(feature = "alloc")]
+pub use self::timeline::*;
+
+#[cfg(feature = "alloc")]
+mod memo;
+#[cfg(feature = "alloc")]
+pub use self::memo::*;
+
+#[cfg(feature = "alloc")]
+mod failure;
+#[cfg(feature = "alloc")]
+pub use self::failure::*;
+
+#[cfg(feature = "alloc")]
+pub mod search;
+
+#[cfg(feature = "alloc")]
+pub mod pattern;
+
+#[cfg(feature = "std")]
 pub mod concurrent;
+#[cfg(feature = "std")]
+mod segmented;
+
+#[cfg(feature = "rayon")]
+mod parallel;
+
+#[cfg(any(all(test, feature = "std"), feature = "testing"))]
+pub mod testing;
 
 #[cfg(feature = "slice")]
 mod slice;
@@ -65,7 +151,7 @@ pub use self::slice::*;
 #[cfg(test)]
 extern crate matches;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "alloc"))]
 mod tests {
   #[test]
   fn basic_test() {
@@ -99,6 +185,93 @@ mod tests {
     }
   }
 
+  #[test]
+  fn forget_keeps_ref_points_test() {
+    use crate::{Record, BacktrackingIterator};
+
+    let mut bt_rec = crate::BacktrackingRecorder::new(1_u8..=6);
+    let kept = {
+      let mut bt_iter = bt_rec.copying();
+      bt_iter.next();
+      bt_iter.next();
+      let kept = bt_iter.get_ref_point();
+      bt_iter.next();
+      kept
+    };
+
+    bt_rec.forget_before(kept);
+
+    let mut bt_iter = bt_rec.copying();
+    assert!(bt_iter.get_oldest_point() == kept);
+    assert!(bt_iter.next().unwrap() == 4_u8);
+    bt_iter.backtrack(kept);
+    assert!(bt_iter.next().unwrap() == 3_u8);
+    // Forgotten points can no longer be backtracked to
+    bt_iter.backtrack(0);
+    assert!(bt_iter.next().unwrap() == 4_u8);
+  }
+
+  #[test]
+  fn push_front_while_backtracked_test() {
+    use crate::{Record, BacktrackingIterator};
+
+    let mut bt_rec = crate::BacktrackingRecorder::new(1_u8..=4);
+    let (before, after) = {
+      let mut bt_iter = bt_rec.copying();
+      let before = bt_iter.get_ref_point();
+      bt_iter.next();
+      bt_iter.next();
+      let after = bt_iter.get_ref_point();
+      bt_iter.next();
+      bt_iter.backtrack(1);
+      (before, after)
+    };
+
+    let shift = bt_rec.push_front(vec![10_u8, 11]);
+    assert!(bt_rec.get_ref_point() == 1);
+    assert!(shift.map(before) == before);
+    assert!(shift.map(after) == after + 2);
+
+    let mut bt_iter = bt_rec.copying();
+    assert!(bt_iter.next().unwrap() == 10_u8);
+    assert!(bt_iter.next().unwrap() == 11_u8);
+    assert!(bt_iter.get_ref_point() == 3);
+    assert!(bt_iter.next().unwrap() == 2_u8);
+    assert!(bt_iter.next().unwrap() == 3_u8);
+    assert!(bt_iter.next().unwrap() == 4_u8);
+
+    // Points taken before the insertion still refer to the same items once shifted
+    bt_iter.backtrack(before);
+    assert!(bt_iter.next().unwrap() == 1_u8);
+    bt_iter.backtrack(shift.map(after));
+    assert!(bt_iter.next().unwrap() == 3_u8);
+  }
+
+  #[test]
+  fn unread_after_forget_test() {
+    use crate::{Record, BacktrackingIterator};
+
+    let mut bt_rec = crate::BacktrackingRecorder::new(1_u8..=3);
+    {
+      let mut bt_iter = bt_rec.copying();
+      bt_iter.next();
+      bt_iter.next();
+      bt_iter.backtrack(1);
+    }
+    bt_rec.forget_before(1);
+
+    bt_rec.unread(9_u8);
+    let mut bt_iter = bt_rec.copying();
+    assert!(bt_iter.get_oldest_point() == 1);
+    assert!(bt_iter.get_ref_point() == 1);
+    assert!(bt_iter.next().unwrap() == 9_u8);
+    assert!(bt_iter.next().unwrap() == 2_u8);
+    assert!(bt_iter.next().unwrap() == 3_u8);
+
+    bt_iter.start_again();
+    assert!(bt_iter.next().unwrap() == 9_u8);
+  }
+
   #[test]
   fn backwalk_test() {
     use crate::{Walkbackable};
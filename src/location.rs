@@ -0,0 +1,334 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{BacktrackingRecorder, CopyingBacktrackingIterator, HistoryShift, ReferencingBacktrackingIterator};
+use crate::record::HistoryChange;
+#[cfg(feature = "serde")]
+use crate::RecorderCheckpoint;
+
+/// A position in a stream of `char`s, as used in error messages. Lines and columns
+/// count from 1, and columns count `char`s rather than bytes. The byte offset counts
+/// from 0, as if the stream were a UTF-8 `str`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Location {
+  pub line: usize,
+  pub column: usize,
+  pub byte_offset: usize,
+}
+
+/// A wrapper around a `char` source which records where lines start as items are
+/// pulled from it. Because a `BacktrackingRecorder` only pulls each item from its
+/// source once, the bookkeeping happens once per item, and replaying history costs
+/// nothing extra.
+///
+/// The wrapper is made by `BacktrackingRecorder::with_line_tracking`, or
+/// `resume_with_line_tracking` for a resumed recorder. The recorder tells it when
+/// items are put into the history by `push_front`, `unread` or `splice_history`, so
+/// locations follow the same `RefPoint`s as the recorder, and when history is
+/// forgotten, so that its tables only cover the remembered history. Forgotten points
+/// have no location.
+/// ```
+/// extern crate backtracking_iterator;
+/// use backtracking_iterator::{BacktrackingIterator, BacktrackingRecorder, Location};
+///
+/// let mut rec = BacktrackingRecorder::with_line_tracking("ab\ncd".chars());
+/// let mut bt = rec.copying();
+/// bt.by_ref().take(4).for_each(drop);
+/// assert!(bt.location(3) == Some(Location { line: 2, column: 1, byte_offset: 3 }));
+/// bt.backtrack(1);
+/// assert!(bt.location(bt.get_ref_point()) == Some(Location { line: 1, column: 2, byte_offset: 1 }));
+/// ```
+pub struct LineTracking<I> {
+  iter: I,
+  /// The point of the next item pulled, which is always the recorder's frontier
+  next_point: usize,
+  /// The oldest point with a location
+  base_point: usize,
+  /// The location of the oldest point
+  base: Location,
+  /// The point of the first item of each line which starts after the oldest point
+  line_starts: Vec<usize>,
+  /// The points of the multi-byte `char`s after the oldest point, along with the
+  /// total number of bytes over one-per-`char` from the oldest point up to and
+  /// including that `char`
+  wide_chars: Vec<(usize, usize)>,
+}
+
+impl<I> LineTracking<I> where I: Iterator<Item=char> {
+  /// Wrap a source whose first item will be at the given point and location
+  fn starting_at(iter: I, point: usize, location: Location) -> Self {
+    LineTracking {
+      iter,
+      next_point: point,
+      base_point: point,
+      base: location,
+      line_starts: vec![],
+      wide_chars: vec![],
+    }
+  }
+}
+
+impl<I> LineTracking<I> {
+  /// The location of the item at the given point, or of the end of everything
+  /// pulled so far. Points after that, and forgotten points, have no location.
+  pub fn location(&self, point: usize) -> Option<Location> {
+    if point < self.base_point || point > self.next_point {
+      return None;
+    }
+
+    // The number of lines starting after the oldest point, at or before this one
+    let lines = self.line_starts.partition_point(|&start| start <= point);
+    let column = match lines {
+      0 => self.base.column + (point - self.base_point),
+      _ => point - self.line_starts[lines - 1] + 1,
+    };
+
+    Some(Location {
+      line: self.base.line + lines,
+      column,
+      byte_offset: self.base.byte_offset + (point - self.base_point) + self.extra_bytes_before(point),
+    })
+  }
+
+  /// The number of bytes over one-per-`char` from the oldest point up to the given
+  /// point
+  fn extra_bytes_before(&self, point: usize) -> usize {
+    match self.wide_chars.partition_point(|&(at, _)| at < point) {
+      0 => 0,
+      index => self.wide_chars[index - 1].1,
+    }
+  }
+
+  /// Follow a change the recorder made to its history
+  fn observe(&mut self, change: HistoryChange<'_, char>) {
+    match change {
+      HistoryChange::Spliced(shift, items) => self.splice(shift, items),
+      HistoryChange::Forgot(point) => self.forget_before(point),
+    }
+  }
+
+  /// Replace the tables for the replaced range with ones for the new items, and
+  /// move everything after it
+  fn splice(&mut self, shift: HistoryShift, items: &[char]) {
+    if shift.start < self.base_point {
+      // Only possible for history restored from a checkpoint before tracking began,
+      // which has no locations anyway
+      self.base_point = shift.map(self.base_point);
+      self.next_point = shift.map(self.next_point);
+      return;
+    }
+
+    let before = self.extra_bytes_before(shift.start);
+    let removed = self.extra_bytes_before(shift.old_end) - before;
+    let mut added = 0;
+    let mut line_starts = vec![];
+    let mut wide_chars = vec![];
+    for (offset, item) in items.iter().enumerate() {
+      if item.len_utf8() > 1 {
+        added += item.len_utf8() - 1;
+        wide_chars.push((shift.start + offset, before + added));
+      }
+      if *item == '\n' {
+        line_starts.push(shift.start + offset + 1);
+      }
+    }
+
+    // A line start at the end of the range comes from a replaced newline
+    let kept = self.line_starts.partition_point(|&start| start <= shift.start);
+    let moved = self.line_starts.partition_point(|&start| start <= shift.old_end);
+    let after = self.line_starts.split_off(moved).into_iter().map(|start| shift.map(start));
+    self.line_starts.truncate(kept);
+    self.line_starts.extend(line_starts.into_iter().chain(after));
+
+    let kept = self.wide_chars.partition_point(|&(at, _)| at < shift.start);
+    let moved = self.wide_chars.partition_point(|&(at, _)| at < shift.old_end);
+    let after = self.wide_chars.split_off(moved).into_iter()
+      .map(|(at, extra)| (shift.map(at), extra - removed + added));
+    self.wide_chars.truncate(kept);
+    self.wide_chars.extend(wide_chars.into_iter().chain(after));
+
+    self.next_point = shift.map(self.next_point);
+  }
+
+  /// Make the given point the oldest one with a location, and drop the table entries
+  /// before it
+  fn forget_before(&mut self, point: usize) {
+    let base = match self.location(point) {
+      Some(base) if point > self.base_point => base,
+      _ => return,
+    };
+
+    let extra = self.extra_bytes_before(point);
+    let kept = self.line_starts.partition_point(|&start| start <= point);
+    self.line_starts.drain(..kept);
+    let kept = self.wide_chars.partition_point(|&(at, _)| at < point);
+    self.wide_chars.drain(..kept);
+    for (_, total) in &mut self.wide_chars {
+      *total -= extra;
+    }
+
+    self.base_point = point;
+    self.base = base;
+  }
+}
+
+impl<I> Iterator for LineTracking<I> where I: Iterator<Item=char> {
+  type Item = char;
+
+  fn next(&mut self) -> Option<char> {
+    let item = self.iter.next()?;
+    let point = self.next_point;
+    self.next_point += 1;
+
+    if item.len_utf8() > 1 {
+      let extra = self.extra_bytes_before(point) + item.len_utf8() - 1;
+      self.wide_chars.push((point, extra));
+    }
+    if item == '\n' {
+      self.line_starts.push(self.next_point);
+    }
+
+    Some(item)
+  }
+}
+
+impl<I> BacktrackingRecorder<LineTracking<I>> where I: Iterator<Item=char> {
+  /// Create a recorder over a `char` source which can give the location of any
+  /// remembered point - see `LineTracking`.
+  pub fn with_line_tracking(chars: I) -> Self {
+    let start = Location { line: 1, column: 1, byte_offset: 0 };
+    let mut recorder = BacktrackingRecorder::new(LineTracking::starting_at(chars, 0, start));
+    recorder.observer = Some(LineTracking::observe);
+    recorder
+  }
+
+  /// Resume a recorder from a checkpoint, as with `resume`, tracking the locations of
+  /// the items pulled from the source from now on. `location` is where the source
+  /// starts - the location of the checkpoint's frontier. The history restored from
+  /// the checkpoint has no locations.
+  #[cfg(feature = "serde")]
+  pub fn resume_with_line_tracking(checkpoint: RecorderCheckpoint<char>, chars: I, location: Location) -> Self {
+    let mut recorder = BacktrackingRecorder::resume(checkpoint, LineTracking::starting_at(chars, 0, location));
+    let frontier = recorder.frontier();
+    recorder.iterator.next_point = frontier;
+    recorder.iterator.base_point = frontier;
+    recorder.observer = Some(LineTracking::observe);
+    recorder
+  }
+
+  /// The location of the item at the given point - see `LineTracking::location`.
+  pub fn location(&self, point: usize) -> Option<Location> {
+    self.iterator.location(point)
+  }
+}
+
+impl<'record, I> CopyingBacktrackingIterator<'record, LineTracking<I>> where I: Iterator<Item=char> {
+  /// The location of the item at the given point - see `LineTracking::location`.
+  pub fn location(&self, point: usize) -> Option<Location> {
+    self.recorder.location(point)
+  }
+}
+
+impl<'record, I> ReferencingBacktrackingIterator<'record, LineTracking<I>> where I: Iterator<Item=char> {
+  /// The location of the item at the given point - see `LineTracking::location`.
+  pub fn location(&self, point: usize) -> Option<Location> {
+    self.recorder.location(point)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{BacktrackingIterator, BacktrackingRecorder, Record};
+  use super::Location;
+
+  #[test]
+  fn wide_chars_and_forget_test() {
+    let mut rec = BacktrackingRecorder::with_line_tracking("é\nxé€y\n".chars());
+    rec.copying().by_ref().take(4).for_each(drop);
+    rec.forget();
+    assert!(rec.location(4) == Some(Location { line: 2, column: 3, byte_offset: 6 }));
+    assert!(rec.location(5).is_none());
+    // Forgotten points have no location
+    assert!(rec.location(3).is_none());
+
+    let mut bt = rec.copying();
+    assert!(bt.by_ref().collect::<String>() == "€y\n");
+    assert!(bt.location(5) == Some(Location { line: 2, column: 4, byte_offset: 9 }));
+    assert!(bt.location(7) == Some(Location { line: 3, column: 1, byte_offset: 11 }));
+    bt.backtrack(4);
+    assert!(bt.location(bt.get_ref_point()) == Some(Location { line: 2, column: 3, byte_offset: 6 }));
+  }
+
+  #[test]
+  fn forget_trims_tables_test() {
+    let mut rec = BacktrackingRecorder::with_line_tracking("é\n".chars().cycle());
+    for _ in 0..100 {
+      rec.copying().by_ref().take(2).for_each(drop);
+      rec.forget();
+    }
+    assert!(rec.iterator.line_starts.len() <= 1);
+    assert!(rec.iterator.wide_chars.is_empty());
+    assert!(rec.location(200) == Some(Location { line: 101, column: 1, byte_offset: 300 }));
+  }
+
+  #[test]
+  fn inserted_items_test() {
+    let mut rec = BacktrackingRecorder::with_line_tracking("ab\ncd".chars());
+    rec.copying().by_ref().take(4).for_each(drop);
+
+    // "a" + "é\n" + "b\ncd"
+    rec.copying().backtrack(1);
+    rec.push_front("é\n".chars());
+    assert!(rec.location(3) == Some(Location { line: 2, column: 1, byte_offset: 4 }));
+    assert!(rec.location(5) == Some(Location { line: 3, column: 1, byte_offset: 6 }));
+
+    // "a" + "é\n" + "b" + "€" + "cd"
+    let shift = rec.splice_history(4..5, "€".chars()).unwrap();
+    assert!(shift.map(5) == 5);
+    assert!(rec.location(5) == Some(Location { line: 2, column: 3, byte_offset: 8 }));
+
+    // The rest of the source follows on from the rewritten history
+    let mut bt = rec.copying();
+    assert!(bt.by_ref().collect::<String>() == "é\nb€cd");
+    assert!(bt.location(6) == Some(Location { line: 2, column: 4, byte_offset: 9 }));
+    assert!(bt.location(7) == Some(Location { line: 2, column: 5, byte_offset: 10 }));
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn resume_test() {
+    let mut rec = BacktrackingRecorder::new("ab\ncd".chars());
+    rec.copying().by_ref().take(4).for_each(drop);
+    rec.forget_before(2);
+    let saved = serde_json::to_string(&rec).unwrap();
+    let checkpoint: crate::RecorderCheckpoint<char> = serde_json::from_str(&saved).unwrap();
+
+    let start = Location { line: 2, column: 2, byte_offset: 4 };
+    let mut rec = BacktrackingRecorder::resume_with_line_tracking(checkpoint, "d".chars(), start);
+    assert!(rec.location(4) == Some(start));
+    assert!(rec.location(3).is_none());
+    rec.copying().by_ref().for_each(drop);
+    assert!(rec.location(5) == Some(Location { line: 2, column: 3, byte_offset: 5 }));
+  }
+}
@@ -0,0 +1,224 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use alloc::collections::BTreeMap;
+
+use super::{BacktrackingIterator, HistoryShift, Record};
+
+/// A packrat memoization table for parsers over a backtracking iterator. For each rule
+/// tried at a `RefPoint`, it stores the rule's result and the `RefPoint` where the rule
+/// finished, so that the rule never needs to be run again at that point.
+///
+/// Entries are keyed by `RefPoint`, so they are only valid for the history they were
+/// made over. When the recorder forgets history, the entries for it are dropped by
+/// the next call to `memoize`, which drops every entry before the oldest point of the
+/// iterator it is given. To drop them straight away, forget through the table with
+/// `forget_in`, or call `forget_before`.
+///
+/// The table is not told about other rewrites of the history. When items are put
+/// into or replaced in a recorder's history with `push_front` or `splice_history`,
+/// move the entries with `remap`, or a lookup may return a result for items which
+/// are no longer there.
+pub struct Memo<Rule, RefPoint, T> {
+  /// The entries for each start point, by rule
+  entries: BTreeMap<RefPoint, BTreeMap<Rule, (T, RefPoint)>>,
+}
+
+impl<Rule, RefPoint, T> Default for Memo<Rule, RefPoint, T> where Rule: Ord, RefPoint: Ord {
+  fn default() -> Self {
+    Memo::new()
+  }
+}
+
+impl<Rule, RefPoint, T> Memo<Rule, RefPoint, T> where Rule: Ord, RefPoint: Ord {
+  /// Create an empty table
+  pub fn new() -> Self {
+    Memo {
+      entries: BTreeMap::new(),
+    }
+  }
+
+  /// Look up the result of a rule tried at the given point, and the point where it
+  /// finished
+  pub fn get(&self, rule: Rule, point: RefPoint) -> Option<&(T, RefPoint)> {
+    self.entries.get(&point)?.get(&rule)
+  }
+
+  /// Store the result of a rule tried at `start`, which finished at `end`
+  pub fn insert(&mut self, rule: Rule, start: RefPoint, result: T, end: RefPoint) {
+    self.entries.entry(start).or_default().insert(rule, (result, end));
+  }
+
+  /// Drop every entry for a rule tried before the given point
+  pub fn forget_before(&mut self, point: &RefPoint) {
+    let stale = match self.entries.keys().next() {
+      Some(oldest) => oldest < point,
+      None => false,
+    };
+    if stale {
+      self.entries = self.entries.split_off(point);
+    }
+  }
+
+  /// Forget the history of a record before the given point, and drop the entries for
+  /// that part of the history
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::{BacktrackingIterator, Cursor, Memo};
+  ///
+  /// let mut cursor = Cursor::from("ab".chars());
+  /// let mut memo = Memo::new();
+  /// cursor.next();
+  /// memo.insert("a", 0, true, 1);
+  ///
+  /// memo.forget_in(&mut cursor, 1);
+  /// assert!(memo.is_empty());
+  /// assert!(cursor.get_oldest_point() == 1);
+  /// ```
+  pub fn forget_in<R>(&mut self, record: &mut R, point: RefPoint) where R: Record<RefPoint=RefPoint> {
+    self.forget_before(&point);
+    record.forget_before(point);
+  }
+
+  /// The number of entries in the table
+  pub fn len(&self) -> usize {
+    self.entries.values().map(BTreeMap::len).sum()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Drop every entry
+  pub fn clear(&mut self) {
+    self.entries.clear();
+  }
+}
+
+impl<Rule, T> Memo<Rule, usize, T> where Rule: Ord {
+  /// Move the entries to follow a rewrite of the history, as reported by
+  /// `BacktrackingRecorder::splice_history` or `push_front`. Entries for rules which
+  /// read any of the replaced items, or ran across the point where new items were
+  /// put in, are dropped.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::{BacktrackingIterator, BacktrackingRecorder, Memo};
+  ///
+  /// let mut rec = BacktrackingRecorder::new(vec!["a", "+", "+", "b"].into_iter());
+  /// rec.copying().by_ref().count();
+  /// let mut memo = Memo::new();
+  /// memo.insert("a", 0, true, 1);
+  /// memo.insert("plus", 1, true, 2);
+  /// memo.insert("b", 3, true, 4);
+  ///
+  /// let shift = rec.splice_history(1..3, vec!["++"]).unwrap();
+  /// memo.remap(&shift);
+  /// assert!(memo.get("a", 0) == Some(&(true, 1)));
+  /// assert!(memo.get("plus", 1).is_none());
+  /// assert!(memo.get("b", 2) == Some(&(true, 3)));
+  ///
+  /// // A rule which finished where items are put in keeps its end
+  /// rec.copying().backtrack(1);
+  /// let shift = rec.push_front(vec!["c"]);
+  /// memo.remap(&shift);
+  /// assert!(memo.get("a", 0) == Some(&(true, 1)));
+  /// assert!(memo.get("b", 3) == Some(&(true, 4)));
+  /// ```
+  pub fn remap(&mut self, shift: &HistoryShift) {
+    let entries = core::mem::take(&mut self.entries);
+    for (start, rules) in entries {
+      for (rule, (result, end)) in rules {
+        if start >= shift.old_end {
+          self.insert(rule, shift.map(start), result, shift.map(end));
+        } else if end <= shift.start {
+          // Finished before the rewrite, so nothing about the entry moves
+          self.insert(rule, start, result, end);
+        }
+      }
+    }
+  }
+}
+
+/// Run a rule at the current point of the iterator, unless its result is already in
+/// the table. On a hit, the stored result is returned, and the iterator is moved to
+/// where the rule finished the first time.
+/// ```
+/// extern crate backtracking_iterator;
+/// use backtracking_iterator::{BacktrackingIterator, BacktrackingRecorder, Memo, memoize};
+///
+/// fn count_as<I>(bt: &mut I, memo: &mut Memo<&str, usize, usize>, runs: &mut usize) -> usize
+///   where I: BacktrackingIterator<Item=char, RefPoint=usize> {
+///   memoize(memo, "as", bt, |bt| {
+///     *runs += 1;
+///     let mut count = 0;
+///     while bt.peek() == Some('a') {
+///       bt.next();
+///       count += 1;
+///     }
+///     count
+///   })
+/// }
+///
+/// let mut rec = BacktrackingRecorder::new("aab".chars());
+/// let mut bt = rec.copying();
+/// let mut memo = Memo::new();
+/// let mut runs = 0;
+///
+/// assert!(count_as(&mut bt, &mut memo, &mut runs) == 2);
+/// bt.start_again();
+/// assert!(count_as(&mut bt, &mut memo, &mut runs) == 2);
+/// assert!(bt.next() == Some('b'));
+/// assert!(runs == 1);
+/// ```
+pub fn memoize<I, Rule, T, F>(memo: &mut Memo<Rule, I::RefPoint, T>, rule: Rule, iter: &mut I, f: F) -> T
+  where I: BacktrackingIterator, I::RefPoint: Ord + Clone, Rule: Ord + Clone, T: Clone, F: FnOnce(&mut I) -> T {
+  memo.forget_before(&iter.get_oldest_point());
+  let start = iter.get_ref_point();
+  if let Some((result, end)) = memo.get(rule.clone(), start.clone()) {
+    let result = result.clone();
+    iter.backtrack(end.clone());
+    return result;
+  }
+
+  let result = f(iter);
+  memo.insert(rule, start, result.clone(), iter.get_ref_point());
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Cursor, Memo, Record, memoize};
+
+  #[test]
+  fn memoize_drops_forgotten_entries_test() {
+    let mut cursor = Cursor::from("abc".chars());
+    let mut memo = Memo::new();
+    memoize(&mut memo, "any", &mut cursor, |cursor| cursor.next());
+    memoize(&mut memo, "any", &mut cursor, |cursor| cursor.next());
+    assert!(memo.len() == 2);
+
+    // Forgetting on the cursor alone, without going through the table
+    cursor.forget();
+    assert!(memoize(&mut memo, "any", &mut cursor, |cursor| cursor.next()) == Some('c'));
+    assert!(memo.len() == 1);
+    assert!(memo.get("any", 2) == Some(&(Some('c'), 3)));
+  }
+}
@@ -0,0 +1,93 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use std::ops::Range;
+
+use rayon::prelude::*;
+
+use crate::BacktrackingIterator;
+use crate::concurrent::ConcurrentCopyingBacktrackingIterator;
+
+impl<Iter> ConcurrentCopyingBacktrackingIterator<Iter>
+  where Iter: Iterator + Send, Iter::Item: Clone + Send + Sync {
+  /// Produce a clone, having pulled from the source until the history is recorded up
+  /// to `point` or the source runs out
+  fn recorded_until(&self, point: usize) -> Self {
+    let mut reader = self.clone();
+    reader.record_until(point);
+    reader
+  }
+
+  /// Produce `count` clones of this iterator as a parallel iterator, each backtracked to
+  /// `start`, so that every rayon task can process the stream from there on its own.
+  ///
+  /// If `start` is before the oldest point of this iterator, it can't be backtracked
+  /// to, and the cursors are left at the current position of this iterator instead.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// extern crate rayon;
+  /// use backtracking_iterator::concurrent::ConcurrentCopyingBacktrackingIterator;
+  /// use rayon::prelude::*;
+  ///
+  /// let bt = ConcurrentCopyingBacktrackingIterator::from(1_u32..=100);
+  /// let sums: Vec<u32> = bt.par_cursors(4, 50)
+  ///   .map(|cursor| cursor.sum())
+  ///   .collect();
+  ///
+  /// assert!(sums == vec![(51..=100).sum(); 4]);
+  /// ```
+  pub fn par_cursors(&self, count: usize, start: usize) -> impl IndexedParallelIterator<Item=Self> {
+    let recorded = self.recorded_until(start);
+    let cursors: Vec<Self> = (0..count).map(|_| {
+      let mut cursor = recorded.clone();
+      cursor.backtrack(start);
+      cursor
+    }).collect();
+    cursors.into_par_iter()
+  }
+
+  /// Produce a parallel iterator over the items recorded in the given range of the
+  /// history, pulling from the source first if the range has not been recorded yet.
+  /// The range is cut short if the source runs out, or if it starts before the oldest
+  /// point of this iterator.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// extern crate rayon;
+  /// use backtracking_iterator::concurrent::ConcurrentCopyingBacktrackingIterator;
+  /// use rayon::prelude::*;
+  ///
+  /// let bt = ConcurrentCopyingBacktrackingIterator::from(1_u32..=100);
+  /// let evens: Vec<u32> = bt.par_replay(0..10)
+  ///   .filter(|i| i % 2 == 0)
+  ///   .collect();
+  ///
+  /// assert!(evens == vec![2, 4, 6, 8, 10]);
+  /// ```
+  pub fn par_replay(&self, range: Range<usize>) -> impl IndexedParallelIterator<Item=Iter::Item> {
+    // The reader keeps the range held for as long as the parallel iterator lives
+    let reader = self.recorded_until(range.end);
+    let start = std::cmp::max(range.start, reader.get_oldest_point());
+    let end = std::cmp::max(start, std::cmp::min(range.end, reader.frontier()));
+    (start..end).into_par_iter().map(move |index| {
+      reader.recorded(index).expect("The replayed range is held by the reader").clone()
+    })
+  }
+}
@@ -0,0 +1,379 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! A small backtracking pattern matcher over streams of any item type - the shape of a
+//! regular expression, with predicates on items in place of characters.
+//!
+//! ```
+//! extern crate backtracking_iterator;
+//! use backtracking_iterator::BacktrackingRecorder;
+//! use backtracking_iterator::pattern::{Pattern, capture, eq, plus, pred, seq};
+//!
+//! #[derive(Clone, PartialEq)]
+//! enum Event { Open, Data(u32), Close }
+//!
+//! // An open, then at least one data event, then a close
+//! let pattern = seq(vec![
+//!   eq(Event::Open),
+//!   capture(0, plus(pred(|event| matches!(event, Event::Data(_))))),
+//!   eq(Event::Close),
+//! ]);
+//!
+//! let events = vec![Event::Open, Event::Data(1), Event::Data(2), Event::Close];
+//! let mut rec = BacktrackingRecorder::new(events.into_iter());
+//! let found = pattern.matches(&mut rec.copying()).unwrap();
+//! assert!(found.span == (0..4));
+//! assert!(found.captures[0] == Some(1..3));
+//! ```
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::BacktrackingIterator;
+
+/// A pattern over items of type `T`
+pub enum Pattern<T> {
+  /// Match one item which satisfies the predicate
+  Pred(Box<dyn Fn(&T) -> bool>),
+  /// Match each pattern in turn
+  Seq(Vec<Pattern<T>>),
+  /// Match the first of the patterns which leads to a match
+  Alt(Vec<Pattern<T>>),
+  /// Match the pattern as many times as possible, including none
+  Star(Box<Pattern<T>>),
+  /// Match the pattern as many times as possible, at least once
+  Plus(Box<Pattern<T>>),
+  /// Match the pattern if possible
+  Opt(Box<Pattern<T>>),
+  /// Match the pattern, and record the span it matched under the given index
+  Capture(usize, Box<Pattern<T>>),
+}
+
+/// Match one item which satisfies the predicate
+pub fn pred<T>(predicate: impl Fn(&T) -> bool + 'static) -> Pattern<T> {
+  Pattern::Pred(Box::new(predicate))
+}
+
+/// Match one item which is equal to the given one
+pub fn eq<T>(expected: T) -> Pattern<T> where T: PartialEq + 'static {
+  pred(move |item| *item == expected)
+}
+
+/// Match any one item
+pub fn any<T>() -> Pattern<T> {
+  pred(|_| true)
+}
+
+/// Match each pattern in turn
+pub fn seq<T>(patterns: Vec<Pattern<T>>) -> Pattern<T> {
+  Pattern::Seq(patterns)
+}
+
+/// Match the first of the patterns which leads to a match
+pub fn alt<T>(patterns: Vec<Pattern<T>>) -> Pattern<T> {
+  Pattern::Alt(patterns)
+}
+
+/// Match the pattern as many times as possible, including none
+pub fn star<T>(pattern: Pattern<T>) -> Pattern<T> {
+  Pattern::Star(Box::new(pattern))
+}
+
+/// Match the pattern as many times as possible, at least once
+pub fn plus<T>(pattern: Pattern<T>) -> Pattern<T> {
+  Pattern::Plus(Box::new(pattern))
+}
+
+/// Match the pattern if possible
+pub fn opt<T>(pattern: Pattern<T>) -> Pattern<T> {
+  Pattern::Opt(Box::new(pattern))
+}
+
+/// Match the pattern, and record the span it matched under the given index
+pub fn capture<T>(index: usize, pattern: Pattern<T>) -> Pattern<T> {
+  Pattern::Capture(index, Box::new(pattern))
+}
+
+/// A successful match of a pattern
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match<RefPoint> {
+  /// The span of the whole match
+  pub span: Range<RefPoint>,
+  /// The span of each capture, by index, if that capture took part in the match
+  pub captures: Vec<Option<Range<RefPoint>>>,
+}
+
+impl<T> Pattern<T> {
+  /// The number of capture slots the pattern needs
+  fn capture_count(&self) -> usize {
+    match self {
+      Pattern::Pred(_) => 0,
+      Pattern::Seq(patterns) | Pattern::Alt(patterns) => patterns.iter().map(Pattern::capture_count).max().unwrap_or(0),
+      Pattern::Star(pattern) | Pattern::Plus(pattern) | Pattern::Opt(pattern) => pattern.capture_count(),
+      Pattern::Capture(index, pattern) => core::cmp::max(index + 1, pattern.capture_count()),
+    }
+  }
+
+  /// Try to match the pattern from the current point of the iterator. On a match, the
+  /// iterator is left after the matched items - otherwise, it is backtracked to where
+  /// it started.
+  pub fn matches<I>(&self, iter: &mut I) -> Option<Match<I::RefPoint>>
+    where I: BacktrackingIterator<Item=T>, I::RefPoint: Clone + PartialEq {
+    let start = iter.get_ref_point();
+    let capture_count = self.capture_count();
+    let mut program = vec![];
+    let mut slot_count = 2 * capture_count;
+    self.compile(&mut program, &mut slot_count);
+    program.push(Inst::Match);
+
+    let mut slots = vec![None; slot_count];
+    if run(&program, iter, &mut slots) {
+      let captures = (0..capture_count).map(|index| match (&slots[2 * index], &slots[2 * index + 1]) {
+        (Some(start), Some(end)) => Some(start.clone()..end.clone()),
+        _ => None,
+      }).collect();
+      Some(Match {
+        span: start..iter.get_ref_point(),
+        captures,
+      })
+    } else {
+      iter.backtrack(start);
+      None
+    }
+  }
+
+  /// Find the first match of the pattern, starting from the current point of the
+  /// iterator and moving forward one item at a time. On a match, the iterator is left
+  /// after the matched items - otherwise, it is left at the end.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::BacktrackingRecorder;
+  /// use backtracking_iterator::pattern::{eq, star, seq};
+  ///
+  /// let pattern = seq(vec![eq(1_u8), star(eq(2_u8)), eq(3_u8)]);
+  /// let mut rec = BacktrackingRecorder::new(vec![1_u8, 1, 2, 2, 3, 4].into_iter());
+  /// assert!(pattern.find(&mut rec.copying()).unwrap().span == (1..5));
+  /// ```
+  pub fn find<I>(&self, iter: &mut I) -> Option<Match<I::RefPoint>>
+    where I: BacktrackingIterator<Item=T>, I::RefPoint: Clone + PartialEq {
+    loop {
+      if let Some(found) = self.matches(iter) {
+        return Some(found);
+      }
+      iter.next()?;
+    }
+  }
+
+  /// Compile the pattern onto the end of the program. Captures use the slots
+  /// `2 * index` and `2 * index + 1` for their start and end, and each repetition is
+  /// given a slot of its own after those, counted by `slot_count`.
+  fn compile<'pattern>(&'pattern self, program: &mut Vec<Inst<'pattern, T>>, slot_count: &mut usize) {
+    match self {
+      Pattern::Pred(predicate) => program.push(Inst::Pred(&**predicate)),
+      Pattern::Seq(patterns) => {
+        for pattern in patterns {
+          pattern.compile(program, slot_count);
+        }
+      },
+      Pattern::Alt(patterns) => match patterns.split_last() {
+        Some((last, rest)) => {
+          let mut jumps = vec![];
+          for pattern in rest {
+            let split = program.len();
+            program.push(Inst::Split(split + 1, 0));
+            pattern.compile(program, slot_count);
+            jumps.push(program.len());
+            program.push(Inst::Jmp(0));
+            program[split] = Inst::Split(split + 1, program.len());
+          }
+          last.compile(program, slot_count);
+          for jump in jumps {
+            program[jump] = Inst::Jmp(program.len());
+          }
+        },
+        None => program.push(Inst::Fail),
+      },
+      Pattern::Star(pattern) => pattern.compile_star(program, slot_count),
+      Pattern::Plus(pattern) => {
+        pattern.compile(program, slot_count);
+        pattern.compile_star(program, slot_count);
+      },
+      Pattern::Opt(pattern) => {
+        let split = program.len();
+        program.push(Inst::Split(split + 1, 0));
+        pattern.compile(program, slot_count);
+        program[split] = Inst::Split(split + 1, program.len());
+      },
+      Pattern::Capture(index, pattern) => {
+        program.push(Inst::Save(2 * index));
+        pattern.compile(program, slot_count);
+        program.push(Inst::Save(2 * index + 1));
+      },
+    }
+  }
+
+  /// Compile a repetition of the pattern, including none
+  fn compile_star<'pattern>(&'pattern self, program: &mut Vec<Inst<'pattern, T>>, slot_count: &mut usize) {
+    let start = *slot_count;
+    *slot_count += 1;
+
+    let split = program.len();
+    program.push(Inst::Split(split + 1, 0));
+    program.push(Inst::Save(start));
+    self.compile(program, slot_count);
+    // Each repetition must make progress, or an empty match would repeat forever
+    program.push(Inst::Progress(start));
+    program.push(Inst::Jmp(split));
+    program[split] = Inst::Split(split + 1, program.len());
+  }
+}
+
+/// An instruction of a compiled pattern
+enum Inst<'pattern, T> {
+  /// Match one item which satisfies the predicate
+  Pred(&'pattern dyn Fn(&T) -> bool),
+  /// Carry on from the first instruction, and if that fails, from the second
+  Split(usize, usize),
+  /// Carry on from the given instruction
+  Jmp(usize),
+  /// Store the current point in the given slot
+  Save(usize),
+  /// Fail if the current point is the one stored in the given slot
+  Progress(usize),
+  /// Fail unconditionally
+  Fail,
+  /// The whole pattern has matched
+  Match,
+}
+
+/// What to undo when a match fails
+enum Frame<RefPoint> {
+  /// Backtrack to the point and carry on from the instruction
+  Retry(usize, RefPoint),
+  /// Put the old value back in the slot
+  Restore(usize, Option<RefPoint>),
+}
+
+/// Run a compiled pattern from the current point of the iterator. Choices are kept on
+/// an explicit stack rather than the call stack, so that long repetitions can't
+/// overflow it. On a failure, the iterator is left at an unspecified point.
+fn run<T, I>(program: &[Inst<T>], iter: &mut I, slots: &mut [Option<I::RefPoint>]) -> bool
+  where I: BacktrackingIterator<Item=T>, I::RefPoint: Clone + PartialEq {
+  let mut stack = vec![];
+  let mut pc = 0;
+
+  loop {
+    let progressed = match program[pc] {
+      Inst::Pred(predicate) => match iter.next() {
+        Some(ref item) => predicate(item),
+        None => false,
+      },
+      Inst::Split(first, second) => {
+        stack.push(Frame::Retry(second, iter.get_ref_point()));
+        pc = first;
+        continue;
+      },
+      Inst::Jmp(target) => {
+        pc = target;
+        continue;
+      },
+      Inst::Save(slot) => {
+        let old = slots[slot].replace(iter.get_ref_point());
+        stack.push(Frame::Restore(slot, old));
+        true
+      },
+      Inst::Progress(slot) => slots[slot].as_ref() != Some(&iter.get_ref_point()),
+      Inst::Fail => false,
+      Inst::Match => return true,
+    };
+
+    if progressed {
+      pc += 1;
+      continue;
+    }
+
+    loop {
+      match stack.pop() {
+        Some(Frame::Retry(retry, point)) => {
+          iter.backtrack(point);
+          pc = retry;
+          break;
+        },
+        Some(Frame::Restore(slot, old)) => slots[slot] = old,
+        None => return false,
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{BacktrackingIterator, BacktrackingRecorder};
+  use super::*;
+
+  #[test]
+  fn backtracking_star_test() {
+    // The star has to give back an item for the rest of the pattern to match
+    let pattern = seq(vec![star(any()), capture(0, eq('b')), eq('c')]);
+    let mut rec = BacktrackingRecorder::new("abbbc!".chars());
+    let mut bt = rec.copying();
+    let found = pattern.matches(&mut bt).unwrap();
+    assert!(found.span == (0..5));
+    assert!(found.captures[0] == Some(3..4));
+    assert!(bt.next() == Some('!'));
+  }
+
+  #[test]
+  fn failed_match_backtracks_test() {
+    let pattern = alt(vec![seq(vec![eq(1), eq(2), eq(4)]), seq(vec![eq(1), opt(eq(5)), eq(3)])]);
+    let mut rec = BacktrackingRecorder::new(vec![1, 2, 3].into_iter());
+    let mut bt = rec.copying();
+    assert!(pattern.matches(&mut bt).is_none());
+    assert!(bt.get_ref_point() == 0);
+
+    let pattern = seq(vec![eq(1), plus(eq(2)), eq(3)]);
+    assert!(pattern.matches(&mut bt).unwrap().span == (0..3));
+  }
+
+  #[test]
+  fn empty_star_test() {
+    let pattern = seq(vec![star(star(eq(1))), eq(2)]);
+    let mut rec = BacktrackingRecorder::new(vec![2].into_iter());
+    assert!(pattern.matches(&mut rec.copying()).unwrap().span == (0..1));
+  }
+
+  #[test]
+  fn long_star_test() {
+    // A long repetition runs without growing the stack
+    let items = (0..100_000).map(|i| if i == 99_999 { 2 } else { 1 });
+    let pattern = seq(vec![star(any()), eq(2)]);
+    let mut rec = BacktrackingRecorder::new(items.clone());
+    assert!(pattern.matches(&mut rec.copying()).unwrap().span == (0..100_000));
+
+    let pattern = seq(vec![capture(0, plus(eq(1))), eq(3)]);
+    let mut rec = BacktrackingRecorder::new(items);
+    let mut bt = rec.copying();
+    assert!(pattern.matches(&mut bt).is_none());
+    assert!(bt.get_ref_point() == 0);
+  }
+}
@@ -19,8 +19,56 @@
  * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
-use super::{BacktrackingState, Record, ReferencingBacktrackingIterator, CopyingBacktrackingIterator};
+use super::{BacktrackingState, Expectations, FurthestFailure, Record, ReferencingBacktrackingIterator, CopyingBacktrackingIterator, Stats};
 use self::BacktrackingState::{Progressing, Backtracking};
+use crate::trace::ReplaySpan;
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::RangeBounds;
+use core::ops::Bound::*;
+
+/// How the `RefPoint`s of a history moved when a range of it was replaced, as
+/// reported by `BacktrackingRecorder::splice_history` and `push_front`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistoryShift {
+  /// The start of the replaced range
+  pub start: usize,
+  /// The end of the replaced range, before it was replaced
+  pub old_end: usize,
+  /// The end of the replacement items
+  pub new_end: usize,
+}
+
+impl HistoryShift {
+  /// Map a `RefPoint` from before the splice to the equivalent one after it. Points
+  /// before the range are unchanged, points after it move with the end of the range,
+  /// and points inside it move to the start of the replacement.
+  pub fn map(&self, point: usize) -> usize {
+    if point < self.start {
+      point
+    } else if point < self.old_end {
+      self.start
+    } else {
+      point - self.old_end + self.new_end
+    }
+  }
+}
+
+/// A change to the history of a recorder, which is passed on to the source iterator
+/// if it asked to be told - see `BacktrackingRecorder::observer`
+pub(crate) enum HistoryChange<'history, Item> {
+  /// A range of the history was replaced, with the items now in its place
+  Spliced(HistoryShift, &'history [Item]),
+  /// Every item before the given point was forgotten
+  Forgot(usize),
+}
+
+/// A function which a recorder calls on its source iterator whenever it rewrites or
+/// forgets its history
+pub(crate) type Observer<Iter> = for<'history> fn(&mut Iter, HistoryChange<'history, <Iter as Iterator>::Item>);
 
 /// A wrapper around an existing iterator to give it a historical representation
 /// with the ability to then produce copying and referencing backtracking iterators
@@ -28,7 +76,59 @@ use self::BacktrackingState::{Progressing, Backtracking};
 pub struct BacktrackingRecorder<Iter> where Iter: Iterator {
   pub(crate) iterator: Iter,
   pub(crate) backtracking_vec: Vec<Iter::Item>,
+  /// The number of items which have been forgotten from the front of the history.
+  /// `RefPoint`s count from the first item ever recorded, so the item at `RefPoint`
+  /// `p` is stored at index `p - forgotten`.
+  pub(crate) forgotten: usize,
+  /// The furthest `RefPoint` the cursor has ever reached. Backtracking leaves this
+  /// alone.
+  pub(crate) furthest: usize,
+  /// What was expected at the furthest point anything was expected
+  pub(crate) expectations: Expectations<usize, Cow<'static, str>>,
+  pub(crate) stats: Stats,
   pub(crate) state: BacktrackingState,
+  pub(crate) replay: ReplaySpan,
+  /// Told about every change to the history which doesn't come from pulling an item
+  /// from the source. Sources which keep their own record of the stream, like
+  /// `LineTracking`, use this to stay in step.
+  pub(crate) observer: Option<Observer<Iter>>,
+}
+
+impl<Iter> core::fmt::Debug for BacktrackingRecorder<Iter> where Iter: Iterator, Iter::Item: core::fmt::Debug {
+  /// Show the recorded history and the cursor. The source iterator is not shown.
+  fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    f.debug_struct("BacktrackingRecorder")
+      .field("history", &self.backtracking_vec)
+      .field("forgotten", &self.forgotten)
+      .field("furthest", &self.furthest)
+      .field("state", &self.state)
+      .field("stats", &self.stats)
+      .finish_non_exhaustive()
+  }
+}
+
+impl<Iter> BacktrackingRecorder<Iter> where Iter: Iterator, Iter::Item: core::fmt::Debug {
+  /// Render the remembered history for debugging, one item per line with its
+  /// `RefPoint`. The cursor is marked with `>`, and the number of forgotten items and
+  /// the frontier are shown around the history.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::{BacktrackingIterator, BacktrackingRecorder, Record};
+  ///
+  /// let mut rec = BacktrackingRecorder::new("abcd".chars());
+  /// rec.copying().next();
+  /// rec.forget();
+  /// {
+  ///   let mut bt = rec.copying();
+  ///   bt.next();
+  ///   bt.next();
+  ///   bt.backtrack(2);
+  /// }
+  /// assert!(rec.dump_history() == "  ..  (1 forgotten)\n  1: 'b'\n> 2: 'c'\n  3: (frontier)\n");
+  /// ```
+  pub fn dump_history(&self) -> String {
+    crate::dump::dump_history(&self.backtracking_vec, self.forgotten, self.get_ref_point(), self.frontier())
+  }
 }
 
 impl<Iter> BacktrackingRecorder<Iter> where Iter: Iterator {
@@ -37,10 +137,155 @@ impl<Iter> BacktrackingRecorder<Iter> where Iter: Iterator {
     BacktrackingRecorder {
       iterator,
       backtracking_vec: vec![],
+      forgotten: 0,
+      furthest: 0,
+      expectations: Expectations::new(),
+      stats: Stats::default(),
       state: Progressing,
+      replay: ReplaySpan::default(),
+      observer: None,
+    }
+  }
+
+  /// Tell the observer, if there is one, that the history was rewritten
+  fn spliced(&mut self, shift: HistoryShift) {
+    if let Some(observe) = self.observer {
+      let items = &self.backtracking_vec[shift.start - self.forgotten..shift.new_end - self.forgotten];
+      observe(&mut self.iterator, HistoryChange::Spliced(shift, items));
     }
   }
 
+  /// Tell the observer, if there is one, that the history was forgotten up to the
+  /// oldest remembered point
+  fn forgot(&mut self) {
+    if let Some(observe) = self.observer {
+      observe(&mut self.iterator, HistoryChange::Forgot(self.forgotten));
+    }
+  }
+
+  /// The `RefPoint` one past the newest item in the history
+  pub(crate) fn frontier(&self) -> usize {
+    self.forgotten + self.backtracking_vec.len()
+  }
+
+  /// Note that the cursor has reached the given point
+  pub(crate) fn reached(&mut self, point: usize) {
+    self.furthest = core::cmp::max(self.furthest, point);
+  }
+
+  /// The furthest `RefPoint` the cursor has ever reached, even if it has since
+  /// backtracked. When a parser fails after backtracking through every
+  /// alternative, this is how far into the input it got.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::{BacktrackingIterator, BacktrackingRecorder};
+  ///
+  /// let mut rec = BacktrackingRecorder::new("abc".chars());
+  /// {
+  ///   let mut bt = rec.copying();
+  ///   bt.next();
+  ///   bt.next();
+  ///   bt.start_again();
+  ///   bt.next();
+  /// }
+  /// assert!(rec.furthest_point() == 2);
+  /// ```
+  pub fn furthest_point(&self) -> usize {
+    self.furthest
+  }
+
+  /// Register that `expected` would have matched at `point`. Only the expectations at
+  /// the furthest point anything was expected are kept - see `Expectations`.
+  pub fn expect(&mut self, point: usize, expected: impl Into<Cow<'static, str>>) {
+    self.expectations.expect(point, expected.into());
+  }
+
+  /// The merged error for a parse over the history: everything expected at the
+  /// furthest point anything was expected, and how far the cursor got.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::{BacktrackingIterator, BacktrackingRecorder};
+  ///
+  /// let mut rec = BacktrackingRecorder::new("(1]".chars());
+  /// {
+  ///   let mut bt = rec.copying();
+  ///   bt.next();
+  ///   bt.next();
+  ///   let point = bt.get_ref_point();
+  ///   if bt.next() != Some(')') {
+  ///     bt.expect(point, "')'");
+  ///     bt.expect(point, format!("digit after {}", 1));
+  ///   }
+  ///   bt.start_again();
+  ///   bt.next();
+  ///   bt.expect(1, "letter");
+  /// }
+  ///
+  /// let failure = rec.failure();
+  /// assert!(failure.to_string() == "at 2: expected ')' or digit after 1");
+  /// assert!(failure.reached == 3);
+  /// ```
+  pub fn failure(&self) -> FurthestFailure<usize, Cow<'static, str>> {
+    self.expectations.clone().failure_at(self.furthest)
+  }
+
+  /// Note that an item was pulled from the source and recorded
+  pub(crate) fn count_pull(&mut self) {
+    self.stats.pulled += 1;
+    self.stats.peak_history = core::cmp::max(self.stats.peak_history, self.backtracking_vec.len());
+  }
+
+  /// Move the cursor back to the given point, if it is remembered
+  pub(crate) fn backtrack_to(&mut self, position: usize) {
+    if position >= self.forgotten && position != self.get_ref_point() {
+      trace_event!(from = self.get_ref_point(), to = position, history_len = self.backtracking_vec.len(), "backtrack");
+      if position < self.frontier() {
+        trace_event!(point = position, frontier = self.frontier(), "replay started");
+        self.replay.open(position, self.frontier());
+      }
+      self.state = Backtracking { position };
+      self.stats.backtracks += 1;
+    }
+  }
+
+  /// Move the cursor back to where it was before a peek. This isn't counted or
+  /// traced as a backtrack.
+  pub(crate) fn undo_peek(&mut self, position: usize) {
+    self.state = Backtracking { position };
+  }
+
+  /// Note that a replay has caught up with the newest item in the history
+  pub(crate) fn reached_frontier(&mut self) {
+    trace_event!(point = self.frontier(), history_len = self.backtracking_vec.len(), "frontier reached");
+    self.state = Progressing;
+    self.replay.close();
+  }
+
+  /// How the recorder has been used so far
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::{BacktrackingIterator, BacktrackingRecorder, Record};
+  ///
+  /// let mut rec = BacktrackingRecorder::new(1_u8..=3);
+  /// {
+  ///   let mut bt = rec.copying();
+  ///   bt.next();
+  ///   bt.next();
+  ///   bt.backtrack(1);
+  ///   bt.backtrack(1);
+  ///   bt.next();
+  ///   bt.peek();
+  /// }
+  /// rec.forget();
+  ///
+  /// let stats = rec.stats();
+  /// assert!(stats.pulled == 3 && stats.replayed == 1 && stats.backtracks == 1);
+  /// assert!(stats.forgets == 1 && stats.forgotten == 3 && stats.peak_history == 3);
+  /// ```
+  pub fn stats(&self) -> Stats {
+    self.stats
+  }
+
   pub fn referencing<'record>(&'record mut self) -> ReferencingBacktrackingIterator<'record, Iter> {
     ReferencingBacktrackingIterator::new(self)
   }
@@ -49,6 +294,100 @@ impl<Iter> BacktrackingRecorder<Iter> where Iter: Iterator {
     CopyingBacktrackingIterator::new(self)
   }
 
+  /// Put the given items into the stream at the current point, so that they are
+  /// yielded in order by the next calls to `next()`, before the rest of the history
+  /// and the source.
+  ///
+  /// `RefPoint`s before the current point are unaffected, and those at or after it
+  /// move forward by the number of items added, as described by the returned shift.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::{BacktrackingIterator, BacktrackingRecorder};
+  ///
+  /// let mut rec = BacktrackingRecorder::new(vec![1_u8, 4].into_iter());
+  /// rec.copying().next(); // 1_u8
+  /// rec.push_front(vec![2_u8, 3]);
+  ///
+  /// let mut bt = rec.copying();
+  /// assert!(bt.by_ref().collect::<Vec<_>>() == vec![2_u8, 3, 4]);
+  /// bt.start_again();
+  /// assert!(bt.collect::<Vec<_>>() == vec![1_u8, 2, 3, 4]);
+  /// ```
+  pub fn push_front(&mut self, items: impl IntoIterator<Item=Iter::Item>) -> HistoryShift {
+    // A cursor past the end of the history will continue from the end
+    let position = core::cmp::min(self.get_ref_point(), self.frontier());
+    let index = position - self.forgotten;
+    let old_len = self.backtracking_vec.len();
+    self.backtracking_vec.splice(index..index, items);
+    self.stats.peak_history = core::cmp::max(self.stats.peak_history, self.backtracking_vec.len());
+    let added = self.backtracking_vec.len() - old_len;
+    if self.furthest > position {
+      self.furthest += added;
+    }
+    self.expectations.map_point(|point| if point > position { point + added } else { point });
+    self.state = Backtracking { position };
+    let shift = HistoryShift { start: position, old_end: position, new_end: position + added };
+    self.spliced(shift);
+    shift
+  }
+
+  /// Put a single item into the stream at the current point, so that it is yielded
+  /// by the next call to `next()`. See `push_front`.
+  pub fn unread(&mut self, item: Iter::Item) {
+    self.push_front(core::iter::once(item));
+  }
+
+  /// Replace a range of `RefPoint`s in the history with new items, and return how the
+  /// `RefPoint`s after the range moved. The current point is moved the same way.
+  ///
+  /// Returns `None`, without changing the history, if the range is not inside the
+  /// remembered history.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::{BacktrackingIterator, BacktrackingRecorder};
+  ///
+  /// let mut rec = BacktrackingRecorder::new(vec!["a", "+", "+", "b"].into_iter());
+  /// rec.copying().by_ref().count();
+  ///
+  /// // Merge the two tokens into one
+  /// let shift = rec.splice_history(1..3, vec!["++"]).unwrap();
+  /// assert!(shift.map(3) == 2);
+  ///
+  /// let mut bt = rec.copying();
+  /// bt.start_again();
+  /// assert!(bt.collect::<Vec<_>>() == vec!["a", "++", "b"]);
+  /// ```
+  pub fn splice_history(&mut self, range: impl RangeBounds<usize>, replacement: impl IntoIterator<Item=Iter::Item>) -> Option<HistoryShift> {
+    let start = match range.start_bound() {
+      Included(&start) => start,
+      Excluded(&start) => start + 1,
+      Unbounded => self.forgotten,
+    };
+    let old_end = match range.end_bound() {
+      Included(&end) => end + 1,
+      Excluded(&end) => end,
+      Unbounded => self.frontier(),
+    };
+
+    if start < self.forgotten || start > old_end || old_end > self.frontier() {
+      return None;
+    }
+
+    let old_len = self.backtracking_vec.len();
+    self.backtracking_vec.splice(start - self.forgotten..old_end - self.forgotten, replacement);
+    let new_end = old_end + self.backtracking_vec.len() - old_len;
+    self.stats.peak_history = core::cmp::max(self.stats.peak_history, self.backtracking_vec.len());
+
+    let shift = HistoryShift { start, old_end, new_end };
+    self.furthest = shift.map(self.furthest);
+    self.expectations.map_point(|point| shift.map(point));
+    if let Backtracking { position } = self.state {
+      self.state = Backtracking { position: shift.map(position) };
+    }
+    self.spliced(shift);
+    Some(shift)
+  }
+
   /// Take all items out of the history.
   /// ```
   /// extern crate backtracking_iterator;
@@ -70,13 +409,21 @@ impl<Iter> BacktrackingRecorder<Iter> where Iter: Iterator {
   pub fn drain_history(&mut self) -> Vec<Iter::Item> {
     // What happes when a `Drain` iterator is leaked is not defined
     // so to guard, we collect it into a vec before returning
-    self.backtracking_vec.drain(..).collect()
+    let history: Vec<Iter::Item> = self.backtracking_vec.drain(..).collect();
+    self.forgotten += history.len();
+    debug_event!(before = self.forgotten, items = history.len(), history_len = 0, "forget");
+    self.stats.forgets += 1;
+    self.stats.forgotten += history.len();
+    self.state = Progressing;
+    self.replay.close();
+    self.forgot();
+    history
   }
 }
 
 impl<Iter, Item> IntoIterator for BacktrackingRecorder<Iter> where Iter: Iterator<Item=Item> + IntoIterator<Item=Item> {
   type Item = Item;
-  type IntoIter = std::iter::Chain<std::vec::IntoIter<Item>, Iter::IntoIter>;
+  type IntoIter = core::iter::Chain<vec::IntoIter<Item>, Iter::IntoIter>;
 
   /// Destroy the record and return an iterator which starts from the beginning
   /// of the history and chains into the originally-given iterator
@@ -110,21 +457,39 @@ impl<Iter> Record for BacktrackingRecorder<Iter> where Iter: Iterator {
   
   fn get_ref_point(&self) -> usize {
     match self.state {
-        Progressing => self.backtracking_vec.len(),
+        Progressing => self.frontier(),
         Backtracking { position } => position,
     }
   }
 
   fn forget_before(&mut self, position: usize) {
-    if position <= self.backtracking_vec.len() {
+    if self.forgotten <= position && position <= self.frontier() {
       //Split the history at the given point
-      let kept = self.backtracking_vec.split_off(position);
+      let kept = self.backtracking_vec.split_off(position - self.forgotten);
       //Keep the second half
       self.backtracking_vec = kept;
+      debug_event!(before = position, items = position - self.forgotten, history_len = self.backtracking_vec.len(), "forget");
+      self.stats.forgets += 1;
+      self.stats.forgotten += position - self.forgotten;
+      self.forgotten = position;
+
+      if let Backtracking { position: current } = self.state {
+        if current < position {
+          self.state = Backtracking { position };
+        }
+      }
+      self.forgot();
     }
   }
 
   fn forget(&mut self) {
+    debug_event!(before = self.frontier(), items = self.backtracking_vec.len(), history_len = 0, "forget");
+    self.forgotten = self.frontier();
+    self.stats.forgets += 1;
+    self.stats.forgotten += self.backtracking_vec.len();
     self.backtracking_vec.clear();
+    self.state = Progressing;
+    self.replay.close();
+    self.forgot();
   }
 }
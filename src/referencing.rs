@@ -19,13 +19,20 @@
  * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
-use super::BacktrackingRecorder;
+use alloc::borrow::Cow;
+use alloc::string::String;
+
+use super::{BacktrackingRecorder, Stats};
 use super::BacktrackingState::*;
 
 /// An iterator over a historical record which produces references to historical
 /// elements
+///
+/// Unlike the copying iterator, this has no `push_front` or `unread`. Putting items
+/// into the history moves the items after them, which the references already yielded
+/// would still point at. Call them on the recorder once the iterator is gone instead.
 pub struct ReferencingBacktrackingIterator<'record, Iter> where Iter: Iterator {
-  recorder: &'record mut BacktrackingRecorder<Iter>,
+  pub(crate) recorder: &'record mut BacktrackingRecorder<Iter>,
 }
 
 impl<'record, Iter> ReferencingBacktrackingIterator<'record, Iter> where Iter: Iterator {
@@ -34,6 +41,22 @@ impl<'record, Iter> ReferencingBacktrackingIterator<'record, Iter> where Iter: I
       recorder,
     }
   }
+
+  /// The furthest point reached - see `BacktrackingRecorder::furthest_point`.
+  pub fn furthest_point(&self) -> usize {
+    self.recorder.furthest_point()
+  }
+
+  /// Register that `expected` would have matched at `point` - see
+  /// `BacktrackingRecorder::expect`.
+  pub fn expect(&mut self, point: usize, expected: impl Into<Cow<'static, str>>) {
+    self.recorder.expect(point, expected)
+  }
+
+  /// How the recorder has been used - see `BacktrackingRecorder::stats`.
+  pub fn stats(&self) -> Stats {
+    self.recorder.stats()
+  }
 }
 
 impl<'record, Iter> Iterator for ReferencingBacktrackingIterator<'record, Iter> where Iter: Iterator, Iter::Item: 'record {
@@ -59,25 +82,46 @@ impl<'record, Iter> Iterator for ReferencingBacktrackingIterator<'record, Iter>
       Progressing => {
         if let Some(val) = self.recorder.iterator.next() {
           self.recorder.backtracking_vec.push(val);
+          self.recorder.count_pull();
+          let frontier = self.recorder.frontier();
+          self.recorder.reached(frontier);
           Some(unsafe_backtracking_index!(self.recorder.backtracking_vec.len() - 1))
         } else {
+          debug_event!(frontier = self.recorder.frontier(), "source exhausted");
           None
         }
       },
       Backtracking { position } => {
-        if position >= self.recorder.backtracking_vec.len() {
-          self.recorder.state = Progressing;
+        if position >= self.recorder.frontier() {
+          self.recorder.reached_frontier();
           self.next()
         } else {
           let new_position = position + 1;
           self.recorder.state = Backtracking { position: new_position };
-          Some(unsafe_backtracking_index!(position))
+          self.recorder.reached(new_position);
+          self.recorder.stats.replayed += 1;
+          Some(unsafe_backtracking_index!(position - self.recorder.forgotten))
         }
       },
     }
   }
 }
 
+impl<'record, Iter> core::fmt::Debug for ReferencingBacktrackingIterator<'record, Iter> where Iter: Iterator, Iter::Item: core::fmt::Debug {
+  fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    f.debug_struct("ReferencingBacktrackingIterator")
+      .field("recorder", &*self.recorder)
+      .finish()
+  }
+}
+
+impl<'record, Iter> ReferencingBacktrackingIterator<'record, Iter> where Iter: Iterator, Iter::Item: core::fmt::Debug {
+  /// Render the history for debugging - see `BacktrackingRecorder::dump_history`.
+  pub fn dump_history(&self) -> String {
+    self.recorder.dump_history()
+  }
+}
+
 use super::BacktrackingIterator;
 
 impl<'record, Iter> BacktrackingIterator for ReferencingBacktrackingIterator<'record, Iter> where Iter: Iterator {
@@ -85,18 +129,22 @@ impl<'record, Iter> BacktrackingIterator for ReferencingBacktrackingIterator<'re
 
   fn get_ref_point(&self) -> usize {
     match self.recorder.state {
-        Progressing => self.recorder.backtracking_vec.len(),
+        Progressing => self.recorder.frontier(),
         Backtracking { position } => position,
     }
   }
 
   fn get_oldest_point(&self) -> usize {
-    // Always the oldest position
-    0_usize
+    // Everything before this has been forgotten
+    self.recorder.forgotten
   }
 
   fn backtrack(&mut self, position: usize) {
-    self.recorder.state = Backtracking { position };
+    self.recorder.backtrack_to(position);
+  }
+
+  fn undo_peek(&mut self, position: usize) {
+    self.recorder.undo_peek(position);
   }
 }
 
@@ -122,7 +170,7 @@ pub struct ReferencingWalkback<'record, Iter> where Iter: Iterator {
 impl<'record, Iter> ReferencingWalkback<'record, Iter> 
   where Iter: Iterator, Iter::Item: 'record {
   fn new<'history>(backtracker: &'history ReferencingBacktrackingIterator<'record, Iter>) -> Self where 'history : 'record {
-    let history_len = backtracker.recorder.backtracking_vec.len();
+    let history_len = backtracker.recorder.frontier();
     ReferencingWalkback {
       backtracker: backtracker.recorder,
       reverse_position: history_len,
@@ -141,16 +189,26 @@ impl<'history, 'record, Iter> Walkback<'history> for ReferencingWalkback<'record
   }
 }
 
+impl<'record, Iter> core::fmt::Debug for ReferencingWalkback<'record, Iter> where Iter: Iterator, Iter::Item: core::fmt::Debug {
+  fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    f.debug_struct("ReferencingWalkback")
+      .field("position", &self.reverse_position)
+      .field("history", &self.backtracker.backtracking_vec)
+      .field("forgotten", &self.backtracker.forgotten)
+      .finish()
+  }
+}
+
 impl<'history, 'record, Iter> Iterator for ReferencingWalkback<'record, Iter> 
   where Iter: Iterator, Iter::Item: 'record, 'history : 'record {
   type Item = &'record Iter::Item;
 
   fn next(&mut self) -> Option<Self::Item> {
-    if self.reverse_position == 0 {
+    if self.reverse_position == self.backtracker.forgotten {
       None
     } else {
       let new_position = self.reverse_position - 1_usize;
-      let val = &self.backtracker.backtracking_vec[new_position];
+      let val = &self.backtracker.backtracking_vec[new_position - self.backtracker.forgotten];
       self.reverse_position = new_position;
       Some(val)
     }
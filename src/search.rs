@@ -0,0 +1,265 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! A depth-first search driver over a backtracking iterator. Each step of the search
+//! consumes items from the iterator and either finds a solution, fails, or offers
+//! alternatives - when a path ends, the iterator is rewound to the point where the
+//! next alternative was offered.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::BacktrackingIterator;
+
+/// What a step of the search found
+pub enum Step<S, Sol> {
+  /// The path ended in a solution
+  Solution(Sol),
+  /// Try each of these states in order, from the current point
+  Branch(Vec<S>),
+  /// Commit to the current path by discarding the untried alternatives of the choice
+  /// point it came from, then try each of these states in order from the current point
+  Cut(Vec<S>),
+  /// The path ended without a solution, and is pruned
+  Fail,
+}
+
+/// A point in the search where alternatives were offered
+struct ChoicePoint<S, RefPoint> {
+  point: RefPoint,
+  /// The untried alternatives, with the next one last
+  alternatives: Vec<S>,
+}
+
+/// A stack of choice points, each remembering the `RefPoint` where it was made and the
+/// alternatives left to try there
+pub struct ChoiceStack<S, RefPoint> {
+  choices: Vec<ChoicePoint<S, RefPoint>>,
+}
+
+impl<S, RefPoint> Default for ChoiceStack<S, RefPoint> {
+  fn default() -> Self {
+    ChoiceStack::new()
+  }
+}
+
+impl<S, RefPoint> ChoiceStack<S, RefPoint> {
+  pub fn new() -> Self {
+    ChoiceStack {
+      choices: vec![],
+    }
+  }
+
+  /// Add a choice point at `point`, whose alternatives will be tried in order
+  pub fn push(&mut self, point: RefPoint, alternatives: Vec<S>) {
+    if !alternatives.is_empty() {
+      let mut alternatives = alternatives;
+      alternatives.reverse();
+      self.choices.push(ChoicePoint { point, alternatives });
+    }
+  }
+
+  /// Take the next alternative of the newest choice point, with the point it should be
+  /// tried from. Choice points are dropped once they have no alternatives left.
+  pub fn next_alternative(&mut self) -> Option<(RefPoint, S)> where RefPoint: Clone {
+    let choice = self.choices.last_mut()?;
+    let alternative = choice.alternatives.pop().expect("Choice points always have an alternative left");
+    let point = choice.point.clone();
+    if choice.alternatives.is_empty() {
+      self.choices.pop();
+    }
+    Some((point, alternative))
+  }
+
+  /// The number of choice points with alternatives left
+  pub fn depth(&self) -> usize {
+    self.choices.len()
+  }
+
+  /// Drop every choice point above the given depth, with all their alternatives
+  pub fn cut_to(&mut self, depth: usize) {
+    self.choices.truncate(depth);
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.choices.is_empty()
+  }
+}
+
+/// A lazy depth-first search, which yields every solution found. See `explore`.
+pub struct Explore<I, S, Sol, F> where I: BacktrackingIterator {
+  iter: I,
+  stack: ChoiceStack<S, I::RefPoint>,
+  step: F,
+  solution_marker: core::marker::PhantomData<Sol>,
+}
+
+/// Search every path from `start` depth-first. `step` is given the iterator and a
+/// state, and reports what it found - the iterator is rewound to the right point
+/// before each state is stepped. Solutions are found lazily, as the search is iterated.
+/// ```
+/// extern crate backtracking_iterator;
+/// use backtracking_iterator::{BacktrackingIterator, BacktrackingRecorder};
+/// use backtracking_iterator::search::{explore, Step};
+///
+/// // Find every way to split the digits into numbers of one or two digits
+/// let mut rec = BacktrackingRecorder::new("123".chars());
+/// let splits: Vec<Vec<u32>> = explore(rec.copying(), (vec![], 0), |bt, (mut numbers, width): (Vec<u32>, usize)| {
+///   let mut number = 0;
+///   for _ in 0..width {
+///     match bt.next() {
+///       Some(digit) => number = number * 10 + digit.to_digit(10).unwrap(),
+///       None => return Step::Fail,
+///     }
+///   }
+///   if width > 0 {
+///     numbers.push(number);
+///   }
+///
+///   if bt.peek().is_none() {
+///     Step::Solution(numbers)
+///   } else {
+///     Step::Branch(vec![(numbers.clone(), 1), (numbers, 2)])
+///   }
+/// }).collect();
+///
+/// assert!(splits == vec![vec![1, 2, 3], vec![1, 23], vec![12, 3]]);
+/// ```
+pub fn explore<I, S, Sol, F>(iter: I, start: S, step: F) -> Explore<I, S, Sol, F>
+  where I: BacktrackingIterator, I::RefPoint: Clone, F: FnMut(&mut I, S) -> Step<S, Sol> {
+  let mut stack = ChoiceStack::new();
+  stack.push(iter.get_ref_point(), vec![start]);
+  Explore {
+    iter,
+    stack,
+    step,
+    solution_marker: core::marker::PhantomData,
+  }
+}
+
+impl<I, S, Sol, F> Explore<I, S, Sol, F> where I: BacktrackingIterator {
+  /// The choice points of the search which still have alternatives left
+  pub fn choices(&mut self) -> &mut ChoiceStack<S, I::RefPoint> {
+    &mut self.stack
+  }
+
+  /// Stop the search, and get the iterator back
+  pub fn into_inner(self) -> I {
+    self.iter
+  }
+}
+
+impl<I, S, Sol, F> Iterator for Explore<I, S, Sol, F>
+  where I: BacktrackingIterator, I::RefPoint: Clone, F: FnMut(&mut I, S) -> Step<S, Sol> {
+  type Item = Sol;
+
+  fn next(&mut self) -> Option<Sol> {
+    loop {
+      // If the alternative's choice point has more alternatives, it is still on the
+      // stack, and a cut has to drop it
+      let depth_before = self.stack.depth();
+      let (point, state) = self.stack.next_alternative()?;
+      let enclosing_depth = if self.stack.depth() == depth_before {
+        depth_before - 1
+      } else {
+        self.stack.depth()
+      };
+
+      self.iter.backtrack(point);
+      match (self.step)(&mut self.iter, state) {
+        Step::Solution(solution) => return Some(solution),
+        Step::Branch(alternatives) => {
+          let point = self.iter.get_ref_point();
+          self.stack.push(point, alternatives);
+        },
+        Step::Cut(alternatives) => {
+          self.stack.cut_to(enclosing_depth);
+          let point = self.iter.get_ref_point();
+          self.stack.push(point, alternatives);
+        },
+        Step::Fail => {},
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{BacktrackingIterator, BacktrackingRecorder};
+  use super::{explore, Step};
+
+  #[test]
+  fn cut_test() {
+    let mut rec = BacktrackingRecorder::new("123".chars());
+    let splits: Vec<Vec<u32>> = explore(rec.copying(), (vec![], 0), |bt, (mut numbers, width): (Vec<u32>, usize)| {
+      let mut number = 0;
+      for _ in 0..width {
+        match bt.next() {
+          Some(digit) => number = number * 10 + digit.to_digit(10).unwrap(),
+          None => return Step::Fail,
+        }
+      }
+      if width > 0 {
+        numbers.push(number);
+      }
+
+      if bt.peek().is_none() {
+        Step::Solution(numbers)
+      } else if numbers.len() == 1 {
+        // Commit to the first number, whichever width it was
+        Step::Cut(vec![(numbers.clone(), 1), (numbers, 2)])
+      } else {
+        Step::Branch(vec![(numbers.clone(), 1), (numbers, 2)])
+      }
+    }).collect();
+
+    assert!(splits == vec![vec![1, 2, 3], vec![1, 23]]);
+  }
+
+  #[test]
+  fn lazy_test() {
+    #[derive(Clone, Copy)]
+    enum State { Start, Check, Advance }
+
+    let mut steps = 0;
+    let mut rec = BacktrackingRecorder::new(0_u32..);
+    let mut search = explore(rec.copying(), State::Start, |bt, state| {
+      steps += 1;
+      match state {
+        State::Start => Step::Branch(vec![State::Check, State::Advance]),
+        State::Check => match bt.peek() {
+          Some(i) if i % 3 == 0 => Step::Solution(i),
+          _ => Step::Fail,
+        },
+        State::Advance => {
+          bt.next();
+          Step::Branch(vec![State::Check, State::Advance])
+        },
+      }
+    });
+
+    assert!(search.next() == Some(0));
+    assert!(search.next() == Some(3));
+    drop(search);
+    // The search stopped as soon as the second solution was found
+    assert!(steps == 8);
+  }
+}
@@ -0,0 +1,191 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+/// The log2 of the size of the first segment
+const FIRST_SEGMENT_SHIFT: u32 = 5;
+/// Enough segments to index every `usize`
+const SEGMENTS: usize = (usize::BITS - FIRST_SEGMENT_SHIFT) as usize;
+
+/// An append-only history which is stored in segments of doubling size. Segments are
+/// never moved once allocated, so a recorded item can be read without taking a lock
+/// while new items are pushed.
+///
+/// Only one thread may push at a time - the owner is expected to serialize pushes
+/// behind its own lock, as the concurrent iterators do with their source.
+pub(crate) struct SegmentedHistory<T> {
+  segments: Box<[AtomicPtr<T>]>,
+  /// The number of items which have been written, and can be read
+  len: AtomicUsize,
+  /// The number of items at the front which have been freed
+  reclaimed: AtomicUsize,
+  item_marker: PhantomData<T>,
+}
+
+// The history owns its items and hands out shared references to them across threads
+unsafe impl<T> Send for SegmentedHistory<T> where T: Send {}
+unsafe impl<T> Sync for SegmentedHistory<T> where T: Send + Sync {}
+
+/// The segment which holds `index`, and the offset of `index` within it
+fn locate(index: usize) -> (usize, usize) {
+  let shifted = index + (1 << FIRST_SEGMENT_SHIFT);
+  let segment = (usize::BITS - 1 - shifted.leading_zeros() - FIRST_SEGMENT_SHIFT) as usize;
+  (segment, shifted - segment_start(segment) - (1 << FIRST_SEGMENT_SHIFT))
+}
+
+fn segment_capacity(segment: usize) -> usize {
+  1 << (segment + FIRST_SEGMENT_SHIFT as usize)
+}
+
+/// The index of the first item in `segment`
+fn segment_start(segment: usize) -> usize {
+  segment_capacity(segment) - (1 << FIRST_SEGMENT_SHIFT)
+}
+
+impl<T> SegmentedHistory<T> {
+  pub(crate) fn new() -> Self {
+    SegmentedHistory {
+      segments: (0..SEGMENTS).map(|_| AtomicPtr::new(ptr::null_mut())).collect(),
+      len: AtomicUsize::new(0),
+      reclaimed: AtomicUsize::new(0),
+      item_marker: PhantomData,
+    }
+  }
+
+  /// The number of items ever pushed, including any which have been reclaimed
+  pub(crate) fn len(&self) -> usize {
+    self.len.load(Ordering::Acquire)
+  }
+
+  /// The number of items at the front which have been freed
+  pub(crate) fn reclaimed(&self) -> usize {
+    self.reclaimed.load(Ordering::Acquire)
+  }
+
+  /// Get the item at `index`, if it has been pushed.
+  ///
+  /// The item must not have been reclaimed.
+  pub(crate) fn get(&self, index: usize) -> Option<&T> {
+    if index >= self.len() {
+      return None;
+    }
+
+    let (segment, offset) = locate(index);
+    let base = self.segments[segment].load(Ordering::Acquire);
+    debug_assert!(!base.is_null(), "Read from a reclaimed segment of the history");
+    // The segment was written before `len` was published past `index`
+    Some(unsafe { &*base.add(offset) })
+  }
+
+  /// Append an item to the history.
+  ///
+  /// The caller must make sure that no other thread is pushing at the same time.
+  pub(crate) unsafe fn push(&self, value: T) {
+    let index = self.len.load(Ordering::Relaxed);
+    let (segment, offset) = locate(index);
+
+    let mut base = self.segments[segment].load(Ordering::Acquire);
+    if base.is_null() {
+      let storage: Box<[MaybeUninit<T>]> = (0..segment_capacity(segment)).map(|_| MaybeUninit::uninit()).collect();
+      base = Box::into_raw(storage) as *mut T;
+      self.segments[segment].store(base, Ordering::Release);
+    }
+
+    base.add(offset).write(value);
+    self.len.store(index + 1, Ordering::Release);
+  }
+
+  /// Free every whole segment before `point`.
+  ///
+  /// The caller must make sure that no thread will read an item before `point`
+  /// again, and that no other thread is reclaiming at the same time.
+  pub(crate) unsafe fn reclaim_before(&self, point: usize) {
+    let point = std::cmp::min(point, self.len());
+    let (mut segment, _) = locate(self.reclaimed());
+
+    while segment_start(segment + 1) <= point {
+      let base = self.segments[segment].swap(ptr::null_mut(), Ordering::AcqRel);
+      // Mark the segment as reclaimed first, so that a panic while dropping an item
+      // leaks the rest of the segment rather than freeing it twice
+      self.reclaimed.store(segment_start(segment + 1), Ordering::Release);
+      Self::free_segment(base, segment, segment_capacity(segment));
+      segment += 1;
+    }
+  }
+
+  /// Drop the first `initialized` items of a segment, and free it
+  unsafe fn free_segment(base: *mut T, segment: usize, initialized: usize) {
+    for offset in 0..initialized {
+      ptr::drop_in_place(base.add(offset));
+    }
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(base as *mut MaybeUninit<T>, segment_capacity(segment))));
+  }
+}
+
+impl<T> Drop for SegmentedHistory<T> {
+  fn drop(&mut self) {
+    let len = *self.len.get_mut();
+    let (mut segment, _) = locate(*self.reclaimed.get_mut());
+
+    while segment_start(segment) < len {
+      let base = *self.segments[segment].get_mut();
+      let initialized = std::cmp::min(len - segment_start(segment), segment_capacity(segment));
+      unsafe { Self::free_segment(base, segment, initialized) };
+      segment += 1;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{locate, SegmentedHistory};
+
+  #[test]
+  fn locate_test() {
+    assert!(locate(0) == (0, 0));
+    assert!(locate(31) == (0, 31));
+    assert!(locate(32) == (1, 0));
+    assert!(locate(95) == (1, 63));
+    assert!(locate(96) == (2, 0));
+  }
+
+  #[test]
+  fn reclaim_drops_items_test() {
+    use std::rc::Rc;
+
+    let counted = Rc::new(());
+    let history = SegmentedHistory::new();
+    for _ in 0..100 {
+      unsafe { history.push(counted.clone()) };
+    }
+
+    unsafe { history.reclaim_before(50) };
+    assert!(history.reclaimed() == 32);
+    assert!(Rc::strong_count(&counted) == 69);
+
+    drop(history);
+    assert!(Rc::strong_count(&counted) == 1);
+  }
+}
@@ -0,0 +1,93 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use super::{BacktrackingRecorder, CopyingBacktrackingIterator};
+
+use self::items::RcItems;
+
+mod items {
+  use alloc::rc::Rc;
+
+  /// A wrapper around a source which puts each item in an `Rc`. Recording it stores
+  /// the handles, so replaying an item only clones its handle.
+  pub struct RcItems<Iter> {
+    iter: Iter,
+  }
+
+  impl<Iter> RcItems<Iter> where Iter: Iterator {
+    pub fn new(iter: Iter) -> Self {
+      RcItems { iter }
+    }
+  }
+
+  impl<Iter> Iterator for RcItems<Iter> where Iter: Iterator {
+    type Item = Rc<Iter::Item>;
+
+    fn next(&mut self) -> Option<Rc<Iter::Item>> {
+      self.iter.next().map(Rc::new)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+      self.iter.size_hint()
+    }
+  }
+}
+
+impl<Iter> BacktrackingRecorder<RcItems<Iter>> where Iter: Iterator {
+  /// Create a recorder which records shared handles to the items of an existing
+  /// iterator, for use with `shared`. The items don't need to be `Clone`.
+  ///
+  /// The `concurrent` module has a version which uses `Arc` instead -
+  /// `ConcurrentCopyingBacktrackingIterator::shared`.
+  pub fn new_shared(iterator: Iter) -> Self {
+    BacktrackingRecorder::new(RcItems::new(iterator))
+  }
+
+  /// Produce an iterator which yields shared handles to the recorded items. Replaying
+  /// an item only clones its `Rc`, and the handles can be kept after the recorder has
+  /// gone.
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::{BacktrackingIterator, BacktrackingRecorder};
+  ///
+  /// // Not `Clone`
+  /// #[derive(PartialEq, Debug)]
+  /// struct Node(Vec<u32>);
+  ///
+  /// let nodes = vec![Node(vec![1, 2]), Node(vec![3])];
+  /// let mut rec = BacktrackingRecorder::new_shared(nodes.into_iter());
+  /// let first = {
+  ///   let mut bt = rec.shared();
+  ///   let first = bt.next().unwrap();
+  ///   bt.start_again();
+  ///   // The replay is the same node, not a copy of it
+  ///   assert!(std::rc::Rc::ptr_eq(&first, &bt.next().unwrap()));
+  ///   first
+  /// };
+  ///
+  /// // The handle outlives the recorder
+  /// drop(rec);
+  /// assert!(*first == Node(vec![1, 2]));
+  /// ```
+  pub fn shared(&mut self) -> CopyingBacktrackingIterator<'_, RcItems<Iter>> {
+    self.copying()
+  }
+}
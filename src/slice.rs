@@ -21,7 +21,7 @@
 
 // A trait which generifies indexing. The trait is stable, but its methods
 // are not - they are enabled by a feature attribute in the crate.
-use std::slice::SliceIndex;
+use core::slice::SliceIndex;
 
 use crate::traits::BacktrackingIterator;
 
@@ -81,7 +81,7 @@ impl<'slice, Slice: ?Sized> BacktrackingIterator for BacktrackingSlice<'slice, S
   }
 }
 
-use std::ops::{RangeBounds, Range, RangeFull, RangeFrom, RangeInclusive, RangeTo, RangeToInclusive};
+use core::ops::{RangeBounds, Range, RangeFull, RangeFrom, RangeInclusive, RangeTo, RangeToInclusive};
 
 use crate::sliceable::SliceableIterator;
 
@@ -97,7 +97,7 @@ impl<'slice, Slice: ?Sized> SliceableIterator for BacktrackingSlice<'slice, Slic
   type Slice = Slice;
 
   fn slice(&self, range: impl RangeBounds<usize>) -> Option<&Slice> {
-    use std::ops::Bound::*;
+    use core::ops::Bound::*;
 
     match (range.start_bound(), range.end_bound()) {
       (Unbounded, Unbounded) => (..).get(self.slice),
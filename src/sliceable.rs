@@ -21,7 +21,7 @@
 
 use crate::BacktrackingIterator;
 
-use std::ops::RangeBounds;
+use core::ops::RangeBounds;
 
 
 /// A backtracking iterator which represents history in a way that makes it 
@@ -47,7 +47,7 @@ pub trait SliceableIterator: BacktrackingIterator {
 #[macro_export]
 macro_rules! sliceable_indexing {
   (<$($parameter:tt),*>, $a_type:ty) => {
-    impl<$($parameter),*, RefPoint, Range: ::std::ops::RangeBounds<RefPoint>> ::std::ops::Index<Range> for $a_type where Self: SliceableIterator<RefPoint=RefPoint> {
+    impl<$($parameter),*, RefPoint, Range: ::core::ops::RangeBounds<RefPoint>> ::core::ops::Index<Range> for $a_type where Self: SliceableIterator<RefPoint=RefPoint> {
       type Output = <Self as SliceableIterator>::Slice;
     
       fn index(&self, range: Range) -> &Self::Output {
@@ -0,0 +1,81 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use core::ops::Range;
+
+/// The span of history between two `RefPoint`s, from `start` up to but not including
+/// `end`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Span<RefPoint> {
+  pub start: RefPoint,
+  pub end: RefPoint,
+}
+
+impl<RefPoint> Span<RefPoint> {
+  pub fn new(start: RefPoint, end: RefPoint) -> Self {
+    Span { start, end }
+  }
+}
+
+impl<RefPoint> Span<RefPoint> where RefPoint: Ord {
+  /// The smallest span which covers both spans, and anything between them
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::Span;
+  ///
+  /// assert!(Span::new(1, 3).merge(Span::new(5, 6)) == Span::new(1, 6));
+  /// ```
+  pub fn merge(self, other: Self) -> Self {
+    Span {
+      start: core::cmp::min(self.start, other.start),
+      end: core::cmp::max(self.end, other.end),
+    }
+  }
+
+  /// Whether the item at the given point is inside the span
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::Span;
+  ///
+  /// assert!(Span::new(1, 3).contains(&1));
+  /// assert!(!Span::new(1, 3).contains(&3));
+  /// ```
+  pub fn contains(&self, point: &RefPoint) -> bool {
+    self.start <= *point && *point < self.end
+  }
+
+  /// Whether the span has no items in it
+  pub fn is_empty(&self) -> bool {
+    self.start >= self.end
+  }
+}
+
+impl<RefPoint> From<Range<RefPoint>> for Span<RefPoint> {
+  fn from(range: Range<RefPoint>) -> Self {
+    Span::new(range.start, range.end)
+  }
+}
+
+impl<RefPoint> From<Span<RefPoint>> for Range<RefPoint> {
+  fn from(span: Span<RefPoint>) -> Self {
+    span.start..span.end
+  }
+}
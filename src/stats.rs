@@ -0,0 +1,76 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts of how a recorder or a concurrent iterator has been used, for seeing how
+/// much a grammar backtracks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Stats {
+  /// The number of items pulled from the source
+  pub pulled: usize,
+  /// The number of items yielded again from the history
+  pub replayed: usize,
+  /// The number of calls to `backtrack` which moved the cursor. Peeking doesn't count.
+  pub backtracks: usize,
+  /// The number of calls to `forget_before` and `forget` which forgot history
+  pub forgets: usize,
+  /// The number of items removed from the history. For the concurrent iterators, this
+  /// is the number of items which no clone can backtrack to any more.
+  pub forgotten: usize,
+  /// The greatest number of items the history has held at once
+  pub peak_history: usize,
+}
+
+/// The counters behind `Stats` for one clone of a concurrent iterator. Each clone only
+/// counts into its own, so that clones on different threads don't contend over them,
+/// and they are summed when the stats are asked for. They are only used for
+/// reporting, so they don't order any other memory.
+#[cfg(feature = "std")]
+#[derive(Default)]
+pub(crate) struct AtomicStats {
+  pub(crate) pulled: AtomicUsize,
+  pub(crate) replayed: AtomicUsize,
+  pub(crate) backtracks: AtomicUsize,
+  pub(crate) forgets: AtomicUsize,
+  pub(crate) peak_history: AtomicUsize,
+}
+
+#[cfg(feature = "std")]
+impl AtomicStats {
+  pub(crate) fn count(counter: &AtomicUsize) {
+    counter.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub(crate) fn history_len(&self, len: usize) {
+    self.peak_history.fetch_max(len, Ordering::Relaxed);
+  }
+
+  /// Add these counts to a total over several clones
+  pub(crate) fn add_to(&self, total: &mut Stats) {
+    total.pulled += self.pulled.load(Ordering::Relaxed);
+    total.replayed += self.replayed.load(Ordering::Relaxed);
+    total.backtracks += self.backtracks.load(Ordering::Relaxed);
+    total.forgets += self.forgets.load(Ordering::Relaxed);
+    total.peak_history = core::cmp::max(total.peak_history, self.peak_history.load(Ordering::Relaxed));
+  }
+}
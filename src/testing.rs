@@ -0,0 +1,624 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Reusable checks that a `BacktrackingIterator` follows the contract of the trait,
+//! and scripted sources for testing how recorders use the iterators they wrap.
+//!
+//! Each check is given a backtracking iterator over a known sequence of items, and
+//! panics if the iterator does not behave like a simple reference model of that
+//! sequence. The items yielded can be the sequence items themselves or borrows of them.
+
+use std::borrow::Borrow;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{BacktrackingIterator, BacktrackingRecorder, Record};
+
+/// A small xorshift generator, so that the random checks are reproducible from a seed
+struct Rng(u64);
+
+impl Rng {
+  fn new(seed: u64) -> Self {
+    // Xorshift is stuck at 0, so mix the seed into a nonzero state
+    Rng((seed ^ 0x9E37_79B9_7F4A_7C15) | 1)
+  }
+
+  fn below(&mut self, bound: usize) -> usize {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 7;
+    self.0 ^= self.0 << 17;
+    (self.0 % bound as u64) as usize
+  }
+}
+
+/// Check that `next()` yields `expected` in order, followed by `None`.
+pub fn check_yields<B, T>(iter: &mut B, expected: &[T]) where B: BacktrackingIterator, B::Item: Borrow<T>, T: PartialEq + Debug {
+  for (index, item) in expected.iter().enumerate() {
+    match iter.next() {
+      Some(ref actual) => assert!(actual.borrow() == item,
+        "Expected {:?} at index {}, but got {:?}", item, index, actual.borrow()),
+      None => panic!("Expected {:?} at index {}, but the iterator ended", item, index),
+    }
+  }
+  assert!(iter.next().is_none(), "Expected the iterator to end after {} items", expected.len());
+}
+
+/// Check that `start_again()` replays every item yielded since the iterator was made.
+/// The iterator must be fresh, and yield exactly `expected`.
+pub fn check_start_again<B, T>(mut iter: B, expected: &[T]) where B: BacktrackingIterator, B::Item: Borrow<T>, T: PartialEq + Debug {
+  check_yields(&mut iter, expected);
+  iter.start_again();
+  check_yields(&mut iter, expected);
+}
+
+/// Check that `peek()` yields the next item without advancing the iterator. The
+/// iterator must be fresh, and yield exactly `expected`.
+pub fn check_peek<B, T>(mut iter: B, expected: &[T]) where B: BacktrackingIterator, B::Item: Borrow<T>, T: PartialEq + Debug {
+  for (index, item) in expected.iter().enumerate() {
+    for _ in 0..2 {
+      match iter.peek() {
+        Some(ref actual) => assert!(actual.borrow() == item,
+          "Expected to peek {:?} at index {}, but got {:?}", item, index, actual.borrow()),
+        None => panic!("Expected to peek {:?} at index {}, but the iterator ended", item, index),
+      }
+    }
+    iter.next();
+  }
+  assert!(iter.peek().is_none(), "Expected to peek the end after {} items", expected.len());
+}
+
+/// Check that the points produced by walking back over the history can be backtracked
+/// to. The iterator must be fresh, and yield exactly `expected`.
+///
+/// Walkbacks borrow the iterator, so `walk` is given the iterator after every item
+/// has been read, and must return the `RefPoint`s of a walkback over it, in the order
+/// they are produced.
+/// ```
+/// extern crate backtracking_iterator;
+/// use backtracking_iterator::{BacktrackingRecorder, Walkback, Walkbackable};
+/// use backtracking_iterator::testing::check_walkback;
+///
+/// let mut rec = BacktrackingRecorder::new(1_u8..=3);
+/// check_walkback(rec.copying(), &[1, 2, 3], |bt| {
+///   let mut wb = bt.walk_back();
+///   let mut points = vec![];
+///   while wb.next().is_some() {
+///     points.push(wb.get_ref_point());
+///   }
+///   points
+/// });
+/// ```
+pub fn check_walkback<B, T, F>(mut iter: B, expected: &[T], walk: F)
+  where B: BacktrackingIterator, B::Item: Borrow<T>, T: PartialEq + Debug, F: FnOnce(&B) -> Vec<B::RefPoint> {
+  check_yields(&mut iter, expected);
+
+  let points = walk(&iter);
+  assert!(points.len() == expected.len(),
+    "Expected to walk back over {} items, but walked back over {}", expected.len(), points.len());
+
+  for (point, item) in points.into_iter().zip(expected.iter().rev()) {
+    iter.backtrack(point);
+    match iter.next() {
+      Some(ref actual) => assert!(actual.borrow() == item,
+        "Expected {:?} after backtracking to a walkback point, but got {:?}", item, actual.borrow()),
+      None => panic!("Expected {:?} after backtracking to a walkback point, but the iterator ended", item),
+    }
+  }
+}
+
+/// A model of a backtracking iterator over a known sequence
+struct Model<'items, T, RefPoint> {
+  items: &'items [T],
+  position: usize,
+  oldest: usize,
+  /// The model position one past the furthest item read or peeked
+  frontier: usize,
+  /// The number of calls to `next()` or `peek()` which found the end of the items
+  ends: usize,
+  /// `RefPoint`s taken from the iterator, with the model position they refer to
+  saved: Vec<(RefPoint, usize)>,
+}
+
+impl<'items, T, RefPoint> Model<'items, T, RefPoint> where T: PartialEq + Debug, RefPoint: Clone {
+  fn new(items: &'items [T], start: RefPoint) -> Self {
+    Model {
+      items,
+      position: 0,
+      oldest: 0,
+      frontier: 0,
+      ends: 0,
+      saved: vec![(start, 0)],
+    }
+  }
+
+  fn check_next<B>(&mut self, iter: &mut B, step: usize) where B: BacktrackingIterator<RefPoint=RefPoint>, B::Item: Borrow<T> {
+    let expected = self.items.get(self.position);
+    let actual = iter.next();
+    assert!(actual.as_ref().map(Borrow::borrow) == expected,
+      "Step {}: expected next() to yield {:?}, but got {:?}", step, expected, actual.as_ref().map(Borrow::borrow));
+    if expected.is_some() {
+      self.position += 1;
+      self.frontier = core::cmp::max(self.frontier, self.position);
+    } else {
+      self.ends += 1;
+    }
+  }
+
+  fn check_peek<B>(&mut self, iter: &mut B, step: usize) where B: BacktrackingIterator<RefPoint=RefPoint>, B::Item: Borrow<T> {
+    let expected = self.items.get(self.position);
+    let actual = iter.peek();
+    assert!(actual.as_ref().map(Borrow::borrow) == expected,
+      "Step {}: expected peek() to yield {:?}, but got {:?}", step, expected, actual.as_ref().map(Borrow::borrow));
+    if expected.is_some() {
+      self.frontier = core::cmp::max(self.frontier, self.position + 1);
+    } else {
+      self.ends += 1;
+    }
+  }
+
+  fn step<B>(&mut self, iter: &mut B, rng: &mut Rng, step: usize) where B: BacktrackingIterator<RefPoint=RefPoint>, B::Item: Borrow<T> {
+    match rng.below(6) {
+      0 | 1 => self.check_next(iter, step),
+      2 => self.check_peek(iter, step),
+      3 => self.saved.push((iter.get_ref_point(), self.position)),
+      4 => {
+        let (point, position) = self.saved[rng.below(self.saved.len())].clone();
+        iter.backtrack(point);
+        self.position = position;
+      },
+      _ => {
+        iter.start_again();
+        self.position = self.oldest;
+      },
+    }
+  }
+
+  /// Forget everything before the current position, which is at `point`
+  fn forget(&mut self, point: RefPoint) {
+    self.oldest = self.position;
+    let oldest = self.oldest;
+    self.saved.retain(|&(_, position)| position >= oldest);
+    self.saved.push((point, oldest));
+  }
+
+  /// Forget the whole history, which moves the position up to the frontier, at `point`
+  fn forget_all(&mut self, point: RefPoint) {
+    self.position = self.frontier;
+    self.forget(point);
+  }
+}
+
+/// Drive the iterator through a random sequence of `steps` calls to `next()`,
+/// `peek()`, `backtrack()` and `start_again()`, checking it against a model of
+/// `expected` at every step. The iterator must be fresh, and yield exactly `expected`.
+///
+/// The same `seed` always produces the same sequence of calls.
+/// ```
+/// extern crate backtracking_iterator;
+/// use backtracking_iterator::BacktrackingRecorder;
+/// use backtracking_iterator::testing::check_model;
+///
+/// let items: Vec<u32> = (0..20).collect();
+/// let mut rec = BacktrackingRecorder::new(items.clone().into_iter());
+/// check_model(rec.referencing(), &items, 7, 1000);
+/// ```
+pub fn check_model<B, T>(mut iter: B, expected: &[T], seed: u64, steps: usize)
+  where B: BacktrackingIterator, B::RefPoint: Clone, B::Item: Borrow<T>, T: PartialEq + Debug {
+  let mut rng = Rng::new(seed);
+  let mut model = Model::new(expected, iter.get_ref_point());
+
+  for step in 0..steps {
+    model.step(&mut iter, &mut rng, step);
+  }
+}
+
+/// Like `check_model`, but also calls `forget()` on the iterator, after which it must
+/// never replay items from before the point it forgot at.
+pub fn check_model_with_forget<B, T>(mut iter: B, expected: &[T], seed: u64, steps: usize)
+  where B: BacktrackingIterator + Record<RefPoint=<B as BacktrackingIterator>::RefPoint>,
+        <B as BacktrackingIterator>::RefPoint: Clone, B::Item: Borrow<T>, T: PartialEq + Debug {
+  let mut rng = Rng::new(seed);
+  let mut model = Model::new(expected, BacktrackingIterator::get_ref_point(&iter));
+
+  for step in 0..steps {
+    if rng.below(10) == 0 {
+      iter.forget();
+      model.forget(BacktrackingIterator::get_ref_point(&iter));
+    } else {
+      model.step(&mut iter, &mut rng, step);
+    }
+  }
+}
+
+/// Like `check_model_with_forget`, but for a `BacktrackingRecorder`, which can only be
+/// told to forget while no iterator over it is live. The check drives a series of
+/// copying iterators over the recorder, and calls `forget()` on the recorder between
+/// them. The recorder must be fresh, and yield exactly `expected`.
+/// ```
+/// extern crate backtracking_iterator;
+/// use backtracking_iterator::BacktrackingRecorder;
+/// use backtracking_iterator::testing::check_recorder_with_forget;
+///
+/// let items: Vec<u32> = (0..20).collect();
+/// check_recorder_with_forget(BacktrackingRecorder::new(items.clone().into_iter()), &items, 7, 1000);
+/// ```
+pub fn check_recorder_with_forget<Iter, T>(mut rec: BacktrackingRecorder<Iter>, expected: &[T], seed: u64, steps: usize)
+  where Iter: Iterator, Iter::Item: Clone + Borrow<T>, T: PartialEq + Debug {
+  let mut rng = Rng::new(seed);
+  let mut model = Model::new(expected, Record::get_ref_point(&rec));
+  let mut step = 0;
+
+  while step < steps {
+    {
+      let mut iter = rec.copying();
+      while step < steps && rng.below(10) != 0 {
+        model.step(&mut iter, &mut rng, step);
+        step += 1;
+      }
+    }
+    rec.forget();
+    model.forget_all(Record::get_ref_point(&rec));
+    step += 1;
+  }
+}
+
+/// A handle on the number of times a `CountingSource` has been pulled from, which can
+/// be kept after the source is given to a recorder
+#[derive(Clone, Debug)]
+pub struct PullCount(Arc<AtomicUsize>);
+
+impl PullCount {
+  /// The number of calls to `next()` on the source so far
+  pub fn get(&self) -> usize {
+    self.0.load(Ordering::SeqCst)
+  }
+}
+
+/// A source which counts how many times `next()` is called on it.
+/// ```
+/// extern crate backtracking_iterator;
+/// use backtracking_iterator::{BacktrackingIterator, BacktrackingRecorder};
+/// use backtracking_iterator::testing::CountingSource;
+///
+/// let source = CountingSource::new(1_u8..=3);
+/// let pulls = source.pulls();
+/// let mut rec = BacktrackingRecorder::new(source);
+/// let mut bt = rec.copying();
+/// bt.next();
+/// bt.start_again();
+/// bt.next();
+///
+/// // The replayed item came from the history
+/// assert!(pulls.get() == 1);
+/// ```
+pub struct CountingSource<Iter> {
+  iterator: Iter,
+  pulls: PullCount,
+}
+
+impl<Iter> CountingSource<Iter> where Iter: Iterator {
+  pub fn new(iterator: Iter) -> Self {
+    CountingSource {
+      iterator,
+      pulls: PullCount(Arc::new(AtomicUsize::new(0))),
+    }
+  }
+
+  /// Get a handle on the number of pulls from this source
+  pub fn pulls(&self) -> PullCount {
+    self.pulls.clone()
+  }
+}
+
+impl<Iter> Iterator for CountingSource<Iter> where Iter: Iterator {
+  type Item = Iter::Item;
+
+  fn next(&mut self) -> Option<Iter::Item> {
+    self.pulls.0.fetch_add(1, Ordering::SeqCst);
+    self.iterator.next()
+  }
+}
+
+/// A source which is not fused - it follows a script of items, where a `None` in the
+/// script is yielded as the end of the iterator, after which the script carries on.
+/// ```
+/// extern crate backtracking_iterator;
+/// use backtracking_iterator::testing::NonFusedSource;
+///
+/// let mut source = NonFusedSource::new(vec![Some(1_u8), None, Some(2_u8)]);
+/// assert!(source.next() == Some(1_u8));
+/// assert!(source.next() == None);
+/// assert!(source.next() == Some(2_u8));
+/// assert!(source.next() == None);
+/// ```
+pub struct NonFusedSource<Item> {
+  script: std::vec::IntoIter<Option<Item>>,
+}
+
+impl<Item> NonFusedSource<Item> {
+  pub fn new(script: Vec<Option<Item>>) -> Self {
+    NonFusedSource {
+      script: script.into_iter(),
+    }
+  }
+}
+
+impl<Item> Iterator for NonFusedSource<Item> {
+  type Item = Item;
+
+  fn next(&mut self) -> Option<Item> {
+    self.script.next().and_then(|item| item)
+  }
+}
+
+/// A source which panics when asked for the item at the given index, and otherwise
+/// yields the items of the wrapped iterator
+pub struct PanickingSource<Iter> {
+  iterator: Iter,
+  index: usize,
+  panic_at: usize,
+}
+
+impl<Iter> PanickingSource<Iter> where Iter: Iterator {
+  pub fn new(iterator: Iter, panic_at: usize) -> Self {
+    PanickingSource {
+      iterator,
+      index: 0,
+      panic_at,
+    }
+  }
+}
+
+impl<Iter> Iterator for PanickingSource<Iter> where Iter: Iterator {
+  type Item = Iter::Item;
+
+  fn next(&mut self) -> Option<Iter::Item> {
+    if self.index == self.panic_at {
+      panic!("PanickingSource was asked for the item at index {}", self.panic_at);
+    }
+    self.index += 1;
+    self.iterator.next()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{Cursor, Timeline, Walkback, Walkbackable};
+  use crate::concurrent::{ConcurrentCopyingBacktrackingIterator, ConcurrentReferencingBacktrackingIterator};
+
+  fn items() -> Vec<u32> {
+    (0..50).collect()
+  }
+
+  #[test]
+  fn copying_test() {
+    let items = items();
+    let mut rec = BacktrackingRecorder::new(items.clone().into_iter());
+    check_start_again(rec.copying(), &items);
+    let mut rec = BacktrackingRecorder::new(items.clone().into_iter());
+    check_peek(rec.copying(), &items);
+    let mut rec = BacktrackingRecorder::new(items.clone().into_iter());
+    check_walkback(rec.copying(), &items, |bt| {
+      let mut wb = bt.walk_back();
+      let mut points = vec![];
+      while wb.next().is_some() {
+        points.push(wb.get_ref_point());
+      }
+      points
+    });
+
+    for seed in 0..20 {
+      let mut rec = BacktrackingRecorder::new(items.clone().into_iter());
+      check_model(rec.copying(), &items, seed, 500);
+      check_recorder_with_forget(BacktrackingRecorder::new(items.clone().into_iter()), &items, seed, 500);
+    }
+  }
+
+  #[test]
+  fn referencing_test() {
+    let items = items();
+    let mut rec = BacktrackingRecorder::new(items.clone().into_iter());
+    check_start_again(rec.referencing(), &items);
+    let mut rec = BacktrackingRecorder::new(items.clone().into_iter());
+    check_peek(rec.referencing(), &items);
+    let mut rec = BacktrackingRecorder::new(items.clone().into_iter());
+    check_walkback(rec.referencing(), &items, |bt| {
+      let mut wb = bt.walk_back();
+      let mut points = vec![];
+      while wb.next().is_some() {
+        points.push(wb.get_ref_point());
+      }
+      points
+    });
+
+    for seed in 0..20 {
+      let mut rec = BacktrackingRecorder::new(items.clone().into_iter());
+      check_model(rec.referencing(), &items, seed, 500);
+    }
+  }
+
+  #[test]
+  fn array_test() {
+    use crate::ArrayRecorder;
+
+    let items = items();
+    check_start_again(ArrayRecorder::<_, 64>::new(items.clone().into_iter()).copying(), &items);
+    check_peek(ArrayRecorder::<_, 64>::new(items.clone().into_iter()).copying(), &items);
+    check_start_again(ArrayRecorder::<_, 64>::new(items.clone().into_iter()).referencing(), &items);
+    check_peek(ArrayRecorder::<_, 64>::new(items.clone().into_iter()).referencing(), &items);
+
+    for seed in 0..20 {
+      let mut rec = ArrayRecorder::<_, 64>::new(items.clone().into_iter());
+      check_model(rec.copying(), &items, seed, 500);
+      let mut rec = ArrayRecorder::<_, 64>::new(items.clone().into_iter());
+      check_model(rec.referencing(), &items, seed, 500);
+      let mut rec = ArrayRecorder::<_, 64>::new(items.clone().into_iter());
+      check_model_with_forget(rec.copying(), &items, seed, 500);
+    }
+  }
+
+  #[test]
+  fn fallible_test() {
+    use crate::FallibleRecorder;
+
+    let items = items();
+    // Every third item fails once before it succeeds
+    let source = || items.iter().flat_map(|&i| if i % 3 == 0 { vec![Err(i), Ok(i)] } else { vec![Ok(i)] });
+
+    for seed in 0..20 {
+      let mut rec = FallibleRecorder::new(source());
+      check_model(rec.copying().bt_filter_map(Result::ok), &items, seed, 500);
+      let mut rec = FallibleRecorder::new(source());
+      check_model(rec.referencing().bt_filter_map(Result::ok), &items, seed, 500);
+    }
+  }
+
+  #[test]
+  fn shared_test() {
+    let items = items();
+    for seed in 0..20 {
+      let mut rec = BacktrackingRecorder::new_shared(items.clone().into_iter());
+      check_model(rec.shared(), &items, seed, 500);
+      check_model_with_forget(ConcurrentCopyingBacktrackingIterator::shared(items.clone().into_iter()), &items, seed, 500);
+      check_model_with_forget(Cursor::from(items.clone().into_iter().map(std::sync::Arc::new)), &items, seed, 500);
+    }
+  }
+
+  #[test]
+  fn cursor_test() {
+    let items = items();
+    check_start_again(Cursor::from(items.clone().into_iter()), &items);
+    check_peek(Cursor::from(items.clone().into_iter()), &items);
+
+    for seed in 0..20 {
+      check_model_with_forget(Cursor::from(items.clone().into_iter()), &items, seed, 500);
+    }
+  }
+
+  #[test]
+  fn adaptors_test() {
+    use crate::BacktrackingIterator;
+
+    let items = items();
+    let doubled: Vec<u32> = items.iter().map(|i| i * 2).collect();
+    let evens: Vec<u32> = items.iter().cloned().filter(|i| i % 2 == 0).collect();
+
+    for seed in 0..20 {
+      let mut rec = BacktrackingRecorder::new(items.clone().into_iter());
+      check_model(rec.copying().bt_map(|i| i * 2), &doubled, seed, 500);
+      let mut rec = BacktrackingRecorder::new(items.clone().into_iter());
+      check_model(rec.copying().bt_filter(|i| i % 2 == 0).bt_inspect(|_| ()), &evens, seed, 500);
+      let mut rec = BacktrackingRecorder::new(items.clone().into_iter());
+      check_model(rec.copying().bt_filter_map(|i| if i % 2 == 0 { Some(i) } else { None }), &evens, seed, 500);
+    }
+  }
+
+  #[test]
+  fn timeline_test() {
+    let items = items();
+    check_start_again(Timeline::from(items.clone().into_iter()), &items);
+    check_peek(Timeline::from(items.clone().into_iter()), &items);
+
+    for seed in 0..20 {
+      check_model(Timeline::from(items.clone().into_iter()), &items, seed, 500);
+    }
+  }
+
+  #[test]
+  fn recorder_never_repulls_test() {
+    let items = items();
+    for seed in 0..20 {
+      let source = CountingSource::new(items.clone().into_iter());
+      let pulls = source.pulls();
+      let mut rec = BacktrackingRecorder::new(source);
+      let mut rng = Rng::new(seed);
+      let model = {
+        let mut bt = rec.copying();
+        let mut model = Model::new(&items[..], bt.get_ref_point());
+        for step in 0..500 {
+          model.step(&mut bt, &mut rng, step);
+        }
+        model
+      };
+
+      // Every item is pulled exactly once, and the end of the source once for each
+      // time it was reached
+      let recorded = rec.drain_history().len();
+      assert!(recorded == model.frontier);
+      assert!(pulls.get() == recorded + model.ends);
+    }
+
+    let source = CountingSource::new(items.clone().into_iter());
+    let pulls = source.pulls();
+    let mut rec = BacktrackingRecorder::new(source);
+    check_start_again(rec.copying(), &items);
+    // One pull for each item, and one for each time the end was reached
+    assert!(pulls.get() == items.len() + 2);
+  }
+
+  #[test]
+  fn non_fused_source_test() {
+    let mut rec = BacktrackingRecorder::new(NonFusedSource::new(vec![Some(1_u8), None, Some(2_u8)]));
+    let mut bt = rec.copying();
+    assert!(bt.next() == Some(1_u8));
+    assert!(bt.next().is_none());
+    assert!(bt.next() == Some(2_u8));
+    bt.start_again();
+    check_yields(&mut bt, &[1_u8, 2_u8]);
+
+    let mut cursor = Cursor::from(NonFusedSource::new(vec![Some(1_u8), None, Some(2_u8)]));
+    assert!(cursor.next() == Some(1_u8));
+    assert!(cursor.next().is_none());
+    assert!(cursor.next() == Some(2_u8));
+  }
+
+  #[test]
+  fn panicking_source_test() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let mut rec = BacktrackingRecorder::new(PanickingSource::new(1_u8..=3, 2));
+    let mut bt = rec.copying();
+    bt.next();
+    bt.next();
+    assert!(catch_unwind(AssertUnwindSafe(|| bt.next())).is_err());
+
+    // The history from before the panic is intact
+    bt.start_again();
+    assert!(bt.next() == Some(1_u8));
+    assert!(bt.next() == Some(2_u8));
+  }
+
+  #[test]
+  fn concurrent_test() {
+    let items = items();
+    check_start_again(ConcurrentCopyingBacktrackingIterator::from(items.clone().into_iter()), &items);
+    check_peek(ConcurrentCopyingBacktrackingIterator::from(items.clone().into_iter()), &items);
+    check_start_again(ConcurrentReferencingBacktrackingIterator::from(items.clone().into_iter()), &items);
+    check_peek(ConcurrentReferencingBacktrackingIterator::from(items.clone().into_iter()), &items);
+
+    for seed in 0..20 {
+      check_model_with_forget(ConcurrentCopyingBacktrackingIterator::from(items.clone().into_iter()), &items, seed, 500);
+      check_model_with_forget(ConcurrentReferencingBacktrackingIterator::from(items.clone().into_iter()), &items, seed, 500);
+    }
+  }
+}
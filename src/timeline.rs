@@ -0,0 +1,300 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::BacktrackingIterator;
+
+/// An identifier for a branch of a `Timeline`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BranchId(usize);
+
+/// A branch of the timeline, which shares the history of its parent up to the point
+/// where it was forked
+struct Branch<Item> {
+  parent: Option<BranchId>,
+  /// The `RefPoint` in the parent branch where this branch starts to diverge
+  fork: usize,
+  items: Vec<Item>,
+}
+
+/// A backtracking iterator which can record alternative futures. After backtracking,
+/// `branch()` starts a new branch from the current point, and `record()` adds items to
+/// it, leaving the original history intact on its own branch. Together with
+/// `switch_to()`, this makes an undo and redo tree.
+///
+/// The root branch records items from the source iterator. Other branches only hold
+/// the items recorded on them, after the items they share with their parent.
+/// ```
+/// extern crate backtracking_iterator;
+/// use backtracking_iterator::{BacktrackingIterator, Timeline};
+///
+/// let mut timeline = Timeline::from("abc".chars());
+/// let root = timeline.current_branch();
+/// timeline.next(); // 'a'
+/// timeline.next(); // 'b'
+///
+/// // Recording in the middle of the history starts a new branch
+/// timeline.backtrack(1);
+/// timeline.record('x');
+/// let alternative = timeline.current_branch();
+/// assert!(timeline.parent(alternative) == Some(root));
+///
+/// timeline.start_again();
+/// assert!(timeline.by_ref().collect::<String>() == "ax");
+///
+/// // The root branch still has the original history, and carries on from the source
+/// timeline.switch_to(root);
+/// timeline.start_again();
+/// assert!(timeline.by_ref().collect::<String>() == "abc");
+///
+/// timeline.drop_branch(alternative);
+/// assert!(timeline.branches().count() == 1);
+/// ```
+pub struct Timeline<Iter> where Iter: Iterator {
+  iterator: Iter,
+  branches: Vec<Option<Branch<Iter::Item>>>,
+  current: BranchId,
+  position: usize,
+  /// Whether the timeline backtracked and hasn't caught up with the end of the
+  /// branch since
+  replaying: bool,
+}
+
+impl<Iter> From<Iter> for Timeline<Iter> where Iter: Iterator {
+  /// Create a `Timeline` from an existing iterator, with only a root branch.
+  fn from(iterator: Iter) -> Self {
+    Timeline {
+      iterator,
+      branches: vec![Some(Branch { parent: None, fork: 0, items: vec![] })],
+      current: BranchId(0),
+      position: 0,
+      replaying: false,
+    }
+  }
+}
+
+impl<Iter> Timeline<Iter> where Iter: Iterator {
+  fn get_branch(&self, id: BranchId) -> Option<&Branch<Iter::Item>> {
+    self.branches.get(id.0).and_then(Option::as_ref)
+  }
+
+  fn current(&self) -> &Branch<Iter::Item> {
+    self.get_branch(self.current).expect("The current branch is never dropped")
+  }
+
+  /// The `RefPoint` one past the last item of a branch
+  fn branch_end(&self, id: BranchId) -> usize {
+    let branch = self.get_branch(id).expect("Only live branches have an end");
+    branch.fork + branch.items.len()
+  }
+
+  /// The item at the given point of a branch, looking in the parent branches for the
+  /// items it shares with them
+  fn item_at(&self, mut id: BranchId, point: usize) -> Option<&Iter::Item> {
+    loop {
+      let branch = self.get_branch(id)?;
+      if point >= branch.fork {
+        return branch.items.get(point - branch.fork);
+      }
+      id = branch.parent?;
+    }
+  }
+
+  /// The branch currently being iterated over
+  pub fn current_branch(&self) -> BranchId {
+    self.current
+  }
+
+  /// The branch which the given branch was forked from, if it is live and not the root
+  pub fn parent(&self, id: BranchId) -> Option<BranchId> {
+    self.get_branch(id).and_then(|branch| branch.parent)
+  }
+
+  /// List every live branch, starting with the root
+  pub fn branches(&self) -> impl Iterator<Item=BranchId> + '_ {
+    self.branches.iter().enumerate()
+      .filter(|(_, branch)| branch.is_some())
+      .map(|(index, _)| BranchId(index))
+  }
+
+  /// Start a new, empty branch from the current point in the current branch, and
+  /// switch to it
+  pub fn branch(&mut self) -> BranchId {
+    let id = BranchId(self.branches.len());
+    self.branches.push(Some(Branch {
+      parent: Some(self.current),
+      fork: self.position,
+      items: vec![],
+    }));
+    self.current = id;
+    id
+  }
+
+  /// Add an item at the current point, and move past it. If the current point is not
+  /// at the end of the current branch, a new branch is started from it first, so that
+  /// recorded history is never overwritten.
+  pub fn record(&mut self, item: Iter::Item) {
+    if self.position != self.branch_end(self.current) {
+      self.branch();
+    }
+    let current = self.current;
+    self.branches[current.0].as_mut().expect("The current branch is never dropped").items.push(item);
+    self.position = self.branch_end(current);
+  }
+
+  /// Switch to another branch, at the point where it diverges from its parent. Returns
+  /// `false`, without switching, if the branch has been dropped.
+  pub fn switch_to(&mut self, id: BranchId) -> bool {
+    match self.get_branch(id) {
+      Some(branch) => {
+        self.position = branch.fork;
+        self.current = id;
+        true
+      },
+      None => false,
+    }
+  }
+
+  /// Drop a branch and every branch forked from it. If the current branch is dropped,
+  /// the timeline switches to the parent of the dropped branch, at the point where they
+  /// diverge. Returns `false` if the branch is the root or was already dropped.
+  pub fn drop_branch(&mut self, id: BranchId) -> bool {
+    let (parent, fork) = match self.get_branch(id) {
+      Some(&Branch { parent: Some(parent), fork, .. }) => (parent, fork),
+      _ => return false,
+    };
+
+    // Branches are always forked after their parent, so one pass finds every descendant
+    let mut dropped = vec![false; self.branches.len()];
+    dropped[id.0] = true;
+    for index in id.0 + 1..self.branches.len() {
+      if let Some(Branch { parent: Some(parent), .. }) = self.branches[index] {
+        dropped[index] = dropped[parent.0];
+      }
+    }
+
+    for (branch, dropped) in self.branches.iter_mut().zip(dropped.iter()) {
+      if *dropped {
+        *branch = None;
+      }
+    }
+
+    if dropped[self.current.0] {
+      self.current = parent;
+      self.position = fork;
+    }
+    true
+  }
+}
+
+impl<Iter> Iterator for Timeline<Iter> where Iter: Iterator, Iter::Item: Clone {
+  type Item = Iter::Item;
+
+  fn next(&mut self) -> Option<Iter::Item> {
+    if let Some(item) = self.item_at(self.current, self.position) {
+      let item = item.clone();
+      self.position += 1;
+      return Some(item);
+    }
+
+    if self.replaying {
+      trace_event!(point = self.position, "frontier reached");
+      self.replaying = false;
+    }
+
+    // Only the root branch carries on from the source
+    if self.current().parent.is_some() {
+      return None;
+    }
+
+    let item = match self.iterator.next() {
+      Some(item) => item,
+      None => {
+        debug_event!(frontier = self.position, "source exhausted");
+        return None;
+      },
+    };
+    self.record(item.clone());
+    Some(item)
+  }
+}
+
+impl<Iter> BacktrackingIterator for Timeline<Iter> where Iter: Iterator, Iter::Item: Clone {
+  type RefPoint = usize;
+
+  fn get_ref_point(&self) -> usize {
+    self.position
+  }
+
+  fn get_oldest_point(&self) -> usize {
+    0_usize
+  }
+
+  fn backtrack(&mut self, position: usize) {
+    let end = self.branch_end(self.current);
+    if position <= end && position != self.position {
+      trace_event!(from = self.position, to = position, branch_end = end, "backtrack");
+      if position < end {
+        trace_event!(point = position, frontier = end, "replay started");
+        self.replaying = true;
+      }
+      self.position = position;
+    }
+  }
+
+  /// Move back to where the timeline was before a peek. This isn't traced as a
+  /// backtrack.
+  fn undo_peek(&mut self, position: usize) {
+    self.position = position;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{BacktrackingIterator, Timeline};
+
+  #[test]
+  fn drop_descendants_test() {
+    let mut timeline = Timeline::from(1_u8..=3);
+    let root = timeline.current_branch();
+    timeline.next();
+    timeline.next();
+
+    let child = timeline.branch();
+    timeline.record(10);
+    timeline.backtrack(1);
+    timeline.record(20);
+    let grandchild = timeline.current_branch();
+    assert!(timeline.parent(grandchild) == Some(child));
+
+    assert!(!timeline.drop_branch(root));
+    assert!(timeline.drop_branch(child));
+    assert!(!timeline.switch_to(grandchild));
+
+    // The timeline falls back to the root where the dropped branch diverged
+    assert!(timeline.current_branch() == root);
+    assert!(timeline.get_ref_point() == 2);
+    assert!(timeline.next() == Some(3));
+    assert!(timeline.branches().eq(vec![root]));
+  }
+}
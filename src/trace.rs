@@ -0,0 +1,222 @@
+/*
+ * Copyright (c) 2018 Isaac van Bakel
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+// Events about what the iterators are doing, emitted through `tracing` when the
+// `tracing` feature is enabled. All events use this crate as their target, and carry
+// `RefPoint`s and history lengths as fields.
+//
+// Without the feature the macros expand to nothing, so that the fields are never
+// evaluated.
+
+#[cfg(feature = "tracing")]
+macro_rules! trace_event {
+  ($($arg:tt)*) => {
+    tracing::trace!(target: "backtracking_iterator", $($arg)*)
+  };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! debug_event {
+  ($($arg:tt)*) => {
+    tracing::debug!(target: "backtracking_iterator", $($arg)*)
+  };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_event {
+  ($($arg:tt)*) => {};
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! debug_event {
+  ($($arg:tt)*) => {};
+}
+
+/// A span covering a replay of the history, open from the backtrack which started it
+/// until the frontier is reached again, so that subscribers can see how long replays
+/// last. A replay runs over many calls, so the span is never entered. Without the
+/// `tracing` feature this is empty.
+#[cfg(feature = "alloc")]
+#[derive(Default)]
+pub(crate) struct ReplaySpan {
+  #[cfg(feature = "tracing")]
+  span: Option<tracing::Span>,
+}
+
+#[cfg(feature = "alloc")]
+impl ReplaySpan {
+  /// Close the replay in progress, if any, and open a new one from `point`
+  #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+  pub(crate) fn open(&mut self, point: usize, frontier: usize) {
+    #[cfg(feature = "tracing")]
+    {
+      self.span = Some(tracing::trace_span!(target: "backtracking_iterator", "replay", point, frontier));
+    }
+  }
+
+  /// Close the replay in progress, if any
+  pub(crate) fn close(&mut self) {
+    #[cfg(feature = "tracing")]
+    {
+      self.span = None;
+    }
+  }
+}
+
+#[cfg(all(test, feature = "tracing", feature = "std"))]
+mod tests {
+  use crate::{ArrayRecorder, BacktrackingIterator, BacktrackingRecorder, Cursor, FallibleRecorder, Record, Timeline};
+
+  use std::sync::{Arc, Mutex};
+  use tracing::{Event, Metadata, Subscriber};
+  use tracing::span::{Attributes, Id, Record as SpanRecord};
+  use tracing::field::{Field, Visit};
+
+  /// Collects the messages of every event, and the opening and closing of spans
+  #[derive(Clone, Default)]
+  struct Messages(Arc<Mutex<Vec<String>>>);
+
+  impl Visit for Messages {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+      if field.name() == "message" {
+        self.0.lock().unwrap().push(format!("{:?}", value));
+      }
+    }
+  }
+
+  impl Subscriber for Messages {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+      metadata.target() == "backtracking_iterator"
+    }
+    fn new_span(&self, attributes: &Attributes) -> Id {
+      self.0.lock().unwrap().push(format!("open {}", attributes.metadata().name()));
+      Id::from_u64(1)
+    }
+    fn try_close(&self, _: Id) -> bool {
+      self.0.lock().unwrap().push("close".to_string());
+      true
+    }
+    fn record(&self, _: &Id, _: &SpanRecord) {}
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+    fn event(&self, event: &Event) {
+      event.record(&mut self.clone());
+    }
+    fn enter(&self, _: &Id) {}
+    fn exit(&self, _: &Id) {}
+  }
+
+  #[test]
+  fn recorder_events_test() {
+    let messages = Messages::default();
+    tracing::subscriber::with_default(messages.clone(), || {
+      let mut rec = BacktrackingRecorder::new(1_u8..=2);
+      {
+        let mut bt = rec.copying();
+        bt.by_ref().count();
+        bt.backtrack(1);
+        bt.by_ref().count();
+      }
+      rec.forget();
+    });
+
+    let messages = messages.0.lock().unwrap();
+    assert!(*messages == vec![
+      "source exhausted", "backtrack", "replay started", "open replay", "frontier reached",
+      "close", "source exhausted", "forget",
+    ]);
+  }
+
+  #[test]
+  fn cursor_events_test() {
+    let messages = Messages::default();
+    tracing::subscriber::with_default(messages.clone(), || {
+      let mut cursor = Cursor::from(1_u8..=2);
+      cursor.by_ref().take(2).for_each(drop);
+      // Neither a backtrack to the current point nor a peek is traced
+      cursor.backtrack(2);
+      cursor.backtrack(0);
+      cursor.peek();
+      cursor.by_ref().count();
+      cursor.forget();
+    });
+
+    let messages = messages.0.lock().unwrap();
+    assert!(*messages == vec![
+      "backtrack", "replay started", "frontier reached", "source exhausted", "forget",
+    ]);
+  }
+
+  #[test]
+  fn timeline_events_test() {
+    let messages = Messages::default();
+    tracing::subscriber::with_default(messages.clone(), || {
+      let mut timeline = Timeline::from(1_u8..=2);
+      timeline.by_ref().take(2).for_each(drop);
+      timeline.backtrack(2);
+      timeline.backtrack(1);
+      timeline.peek();
+      timeline.by_ref().count();
+    });
+
+    let messages = messages.0.lock().unwrap();
+    assert!(*messages == vec![
+      "backtrack", "replay started", "frontier reached", "source exhausted",
+    ]);
+  }
+
+  #[test]
+  fn array_events_test() {
+    let messages = Messages::default();
+    tracing::subscriber::with_default(messages.clone(), || {
+      let mut rec = ArrayRecorder::<_, 4>::new(1_u8..=2);
+      let mut bt = rec.copying();
+      bt.by_ref().take(2).for_each(drop);
+      bt.backtrack(2);
+      bt.backtrack(1);
+      bt.peek();
+      bt.by_ref().count();
+    });
+
+    let messages = messages.0.lock().unwrap();
+    assert!(*messages == vec![
+      "backtrack", "replay started", "frontier reached", "source exhausted",
+    ]);
+  }
+
+  #[test]
+  fn fallible_events_test() {
+    let messages = Messages::default();
+    tracing::subscriber::with_default(messages.clone(), || {
+      let mut rec = FallibleRecorder::new(vec![Ok(1_u8), Ok(2), Err(())].into_iter());
+      let mut bt = rec.copying();
+      bt.by_ref().take(2).for_each(drop);
+      bt.backtrack(2);
+      bt.backtrack(1);
+      bt.peek();
+      bt.by_ref().count();
+    });
+
+    let messages = messages.0.lock().unwrap();
+    assert!(*messages == vec![
+      "backtrack", "replay started", "frontier reached", "source error", "source exhausted",
+    ]);
+  }
+}
@@ -19,6 +19,9 @@
  * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
+use crate::adaptors::{BtMap, BtFilter, BtFilterMap, BtInspect, Positioned};
+use crate::span::Span;
+
 /// A historical record representation
 pub trait Record {
   /// The type used to refer to positions in the history
@@ -133,9 +136,105 @@ pub trait BacktrackingIterator: Iterator {
   fn peek(&mut self) -> Option<Self::Item> {
     let current = self.get_ref_point();
     let next = self.next();
-    self.backtrack(current);
+    self.undo_peek(current);
     next
   }
+
+  /// Move back to the given point after looking ahead from it, as `peek()` does. This
+  /// is the same as `backtrack()`, except that it isn't counted or traced as a
+  /// backtrack, so iterators which count or trace backtracks should override it.
+  fn undo_peek(&mut self, point: Self::RefPoint) {
+    self.backtrack(point);
+  }
+
+  /// Get the span of everything read since the given point
+  ///
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::{BacktrackingIterator, Span};
+  ///
+  /// let v = vec![1_u8, 2_u8, 3_u8];
+  /// let mut rec = backtracking_iterator::BacktrackingRecorder::new(v.into_iter());
+  /// let mut bt = rec.copying();
+  /// bt.next();
+  /// let checkpoint = bt.get_ref_point();
+  /// bt.next();
+  /// bt.next();
+  /// assert!(bt.consumed_since(checkpoint) == Span::new(1, 3));
+  /// ```
+  fn consumed_since(&self, point: Self::RefPoint) -> Span<Self::RefPoint> {
+    Span::new(point, self.get_ref_point())
+  }
+
+  /// Yield each item along with the point before it, which can be backtracked to in
+  /// order to read the item again. The positioned iterator can still backtrack.
+  ///
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::BacktrackingIterator;
+  ///
+  /// let v = vec!['a', 'b'];
+  /// let mut rec = backtracking_iterator::BacktrackingRecorder::new(v.into_iter());
+  /// let mut positioned = rec.copying().positioned();
+  /// assert!(positioned.next() == Some((0, 'a')));
+  /// assert!(positioned.next() == Some((1, 'b')));
+  /// positioned.backtrack(1);
+  /// assert!(positioned.next() == Some((1, 'b')));
+  /// ```
+  fn positioned(self) -> Positioned<Self> where Self: Sized {
+    Positioned { iter: self }
+  }
+
+  /// Map the items of the iterator, like `Iterator::map`, but keeping the ability to
+  /// backtrack. The mapped iterator uses the same `RefPoint`s as this one.
+  ///
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::BacktrackingIterator;
+  ///
+  /// let v = vec![1_u8, 2_u8];
+  /// let mut rec = backtracking_iterator::BacktrackingRecorder::new(v.into_iter());
+  /// let mut doubled = rec.copying().bt_map(|i| i * 2);
+  /// assert!(doubled.next().unwrap() == 2_u8);
+  /// doubled.start_again();
+  /// assert!(doubled.next().unwrap() == 2_u8);
+  /// assert!(doubled.next().unwrap() == 4_u8);
+  /// ```
+  fn bt_map<B, F>(self, f: F) -> BtMap<Self, F> where Self: Sized, F: FnMut(Self::Item) -> B {
+    BtMap { iter: self, f }
+  }
+
+  /// Skip the items of the iterator which don't match the predicate, like
+  /// `Iterator::filter`, but keeping the ability to backtrack.
+  ///
+  /// ```
+  /// extern crate backtracking_iterator;
+  /// use backtracking_iterator::BacktrackingIterator;
+  ///
+  /// let v = vec![1_u8, 2_u8, 3_u8, 4_u8];
+  /// let mut rec = backtracking_iterator::BacktrackingRecorder::new(v.into_iter());
+  /// let mut evens = rec.copying().bt_filter(|i| i % 2 == 0);
+  /// let here = evens.get_ref_point();
+  /// assert!(evens.next().unwrap() == 2_u8);
+  /// assert!(evens.next().unwrap() == 4_u8);
+  /// evens.backtrack(here);
+  /// assert!(evens.next().unwrap() == 2_u8);
+  /// ```
+  fn bt_filter<P>(self, predicate: P) -> BtFilter<Self, P> where Self: Sized, P: FnMut(&Self::Item) -> bool {
+    BtFilter { iter: self, predicate }
+  }
+
+  /// Map the items of the iterator, skipping those which map to `None`, like
+  /// `Iterator::filter_map`, but keeping the ability to backtrack.
+  fn bt_filter_map<B, F>(self, f: F) -> BtFilterMap<Self, F> where Self: Sized, F: FnMut(Self::Item) -> Option<B> {
+    BtFilterMap { iter: self, f }
+  }
+
+  /// Call a function on every item before it is yielded, like `Iterator::inspect`, but
+  /// keeping the ability to backtrack.
+  fn bt_inspect<F>(self, f: F) -> BtInspect<Self, F> where Self: Sized, F: FnMut(&Self::Item) {
+    BtInspect { iter: self, f }
+  }
 }
 
 /// An iterator that can be walked back on, parameterised for a lifetime